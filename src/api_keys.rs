@@ -0,0 +1,144 @@
+use chrono::NaiveDateTime;
+use openssl::{rand::rand_bytes, sha::sha256};
+use postgres::{error::SqlState, Error};
+
+use crate::db::DB;
+
+
+const KEY_BYTES: usize = 32;
+
+
+pub struct ApiKey {
+    pub name: String,
+    // Requests allowed per hour, unlimited when None.
+    pub quota: Option<i32>,
+    pub requests: i64,
+    pub last_hour: i64,
+    pub created: NaiveDateTime,
+    pub last_used: Option<NaiveDateTime>,
+    pub revoked: bool,
+}
+
+
+#[derive(Debug, PartialEq)]
+pub enum Authorization {
+    Unknown,
+    Allowed,
+    // Seconds until the quota resets.
+    Exceeded(i64),
+}
+
+
+pub fn create_tables(db: &mut DB) -> Result<(), Error> {
+    db.batch_execute("create_api_keys",
+        "create table if not exists api_keys(
+            key_id serial primary key,
+            name varchar(255) unique not null,
+            key_hash char(64) unique not null,
+            quota integer,
+            requests bigint not null default 0,
+            created timestamp not null default now(),
+            last_used timestamp,
+            revoked boolean not null default false
+        );
+        create table if not exists api_usage(
+            key_id integer references api_keys,
+            hour timestamp not null,
+            requests integer not null,
+            primary key (key_id, hour)
+        );")
+}
+
+
+fn hash(key: &str) -> String {
+    sha256(key.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+
+// Creates a key and returns it. Only its hash is stored so it can't be shown again.
+pub fn issue(db: &mut DB, name: &str, quota: Option<i32>) -> Result<String, Box<dyn std::error::Error>> {
+    let mut bytes = [0; KEY_BYTES];
+    rand_bytes(&mut bytes)?;
+    let key = bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+    match db.execute("api_keys_issue", "insert into api_keys(name, key_hash, quota) values ($1, $2, $3)",
+                     &[&name, &hash(&key), &quota]) {
+        Ok(_) => Ok(key),
+        Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => Err(format!("A key named {} already exists", name).into()),
+        Err(e) => Err(e.into())
+    }
+}
+
+
+pub fn revoke(db: &mut DB, name: &str) -> Result<bool, Error> {
+    Ok(db.execute("api_keys_revoke", "update api_keys set revoked = true where name = $1", &[&name])? > 0)
+}
+
+
+pub fn list(db: &mut DB) -> Result<Vec<ApiKey>, Error> {
+    let query =
+        "select name, quota, k.requests, coalesce(u.requests, 0)::bigint, created, last_used, revoked
+        from api_keys k
+        left join api_usage u on u.key_id = k.key_id and u.hour = date_trunc('hour', now()::timestamp)
+        order by name";
+
+    Ok(db.query("api_keys_list", query, &[])?
+        .iter()
+        .map(|row| ApiKey {
+                name: row.get(0),
+                quota: row.get(1),
+                requests: row.get(2),
+                last_hour: row.get(3),
+                created: row.get(4),
+                last_used: row.get(5),
+                revoked: row.get(6),
+            }
+        )
+        .collect())
+}
+
+
+// Records a request made with `key` and checks it against the key's hourly quota. The count is kept in
+// Postgres with a single upsert so every worker and every API process shares the same quota.
+pub fn authorize(db: &mut DB, key: &str) -> Result<Authorization, Error> {
+    let query =
+        "with key as (
+            select key_id, quota from api_keys where key_hash = $1 and not revoked
+        ),
+        usage as (
+            insert into api_usage(key_id, hour, requests)
+            select key_id, date_trunc('hour', now()::timestamp), 1 from key
+            on conflict (key_id, hour) do update set requests = api_usage.requests + 1
+            returning key_id, requests
+        ),
+        total as (
+            update api_keys set requests = requests + 1, last_used = now()
+            where key_id in (select key_id from key)
+        )
+        select quota, requests,
+        extract(epoch from date_trunc('hour', now()::timestamp) + interval '1 hour' - now()::timestamp)::bigint
+        from key
+        join usage using(key_id)";
+
+    let rows = db.query("api_keys_authorize", query, &[&hash(key)])?;
+    let row = match rows.first() {
+        Some(r) => r,
+        None => return Ok(Authorization::Unknown)
+    };
+
+    match row.get::<_, Option<i32>>(0) {
+        Some(quota) if row.get::<_, i32>(1) > quota => Ok(Authorization::Exceeded(row.get(2))),
+        _ => Ok(Authorization::Allowed)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_key() {
+        assert_eq!("2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae", hash("foo"));
+    }
+}
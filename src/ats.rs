@@ -0,0 +1,78 @@
+use chrono::NaiveDate;
+use postgres::Error;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::db::DB;
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AllTimes {
+    pub name: String,
+    pub lowest: Decimal,
+    pub lowest_date: NaiveDate,
+    pub highest: Decimal,
+    pub highest_date: NaiveDate,
+}
+
+
+// All time low and high euro prices for a coin across `prices` and `daily_stats`.
+pub fn query(db: &mut DB, coin: &str) -> Result<Option<AllTimes>, Error> {
+    let query =
+        "with all_ats as (
+            select min(euro) as lowest, max(euro) as ath
+            from prices
+            join coins using(coin_id)
+            where name = $1
+            union select min_euro as lowest, max_euro as ath
+            from daily_stats
+            join coins using(coin_id)
+            where name = $1
+        ),
+        extremes as (
+            select min(lowest) as minimum, max(ath) as ath
+            from all_ats
+        ),
+        lowest as (
+            select time::date as date, euro as price
+            from prices
+            join coins using(coin_id)
+            where euro=(select minimum from extremes)
+            and name = $1
+            union select date, min_euro as price
+            from daily_stats
+            join coins using(coin_id)
+            where min_euro=(select minimum from extremes)
+            and name = $1
+            limit 1
+        ),
+        highest as (
+            select time::date as date, euro as price
+            from prices
+            join coins using(coin_id)
+            where euro=(select ath from extremes)
+            and name = $1
+            union select date, max_euro as price
+            from daily_stats
+            join coins using(coin_id)
+            where max_euro=(select ath from extremes)
+            and name = $1
+            limit 1
+        )
+        select date, price from lowest union select date, price from highest
+        order by price asc";
+
+    let rows = db.query("ats", query, &[&coin])?;
+    if rows.len() < 2 {
+        return Ok(None);
+    }
+
+    let (lowest, highest) = (&rows[0], &rows[1]);
+    Ok(Some(AllTimes {
+        name: coin.to_string(),
+        lowest_date: lowest.get(0),
+        lowest: lowest.get(1),
+        highest_date: highest.get(0),
+        highest: highest.get(1)
+    }))
+}
@@ -0,0 +1,57 @@
+use std::{env, error::Error, process};
+
+use rooney::{api_keys, db};
+
+
+const USAGE: &str = "Usage: apikeys init | issue <name> [hourly quota] | revoke <name> | list";
+
+
+fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = args.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+    let mut db = db::DB::new()?;
+
+    match args.as_slice() {
+        ["init"] => {
+            api_keys::create_tables(&mut db)?;
+            println!("Created API key tables");
+        },
+        ["issue", name] | ["issue", name, _] => {
+            let quota = match args.get(2) {
+                Some(q) => Some(q.parse::<i32>().map_err(|_| format!("Invalid quota: {}", q))?),
+                None => None
+            };
+            let key = api_keys::issue(&mut db, name, quota)?;
+            println!("Issued key for {}: {}", name, key);
+            println!("Store it now, it can't be shown again.");
+        },
+        ["revoke", name] => match api_keys::revoke(&mut db, name)? {
+            true => println!("Revoked key for {}", name),
+            false => return Err(format!("No key named {}", name).into())
+        },
+        ["list"] => {
+            println!("{:<24} {:>10} {:>10} {:>12} {:<20} {:<20}", "name", "quota/h", "this hour", "total",
+                     "created", "last used");
+            for k in api_keys::list(&mut db)? {
+                println!("{:<24} {:>10} {:>10} {:>12} {:<20} {:<20}{}", k.name,
+                         k.quota.map_or("-".to_string(), |q| q.to_string()), k.last_hour, k.requests,
+                         k.created.format("%Y-%m-%d %H:%M:%S"),
+                         k.last_used.map_or("-".to_string(), |t| t.format("%Y-%m-%d %H:%M:%S").to_string()),
+                         if k.revoked { " revoked" } else { "" });
+            }
+        },
+        _ => return Err(USAGE.into())
+    }
+
+    Ok(())
+}
+
+
+fn main() {
+    env_logger::init();
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,64 @@
+use std::cell::RefCell;
+
+use actix_web::{dev::ServiceRequest, web};
+use rooney::api_keys::{self, Authorization};
+use rooney::db::DB;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+
+// Each worker keeps its own key-only connection, reopened on the next request after an error.
+#[derive(Default)]
+pub struct Keys {
+    db: RefCell<Option<DB>>,
+}
+
+
+#[derive(Deserialize)]
+struct KeyParams {
+    api_key: Option<String>,
+}
+
+
+// Keys can be sent as a query parameter because browsers can't set headers on an EventSource.
+fn key(req: &ServiceRequest) -> Option<String> {
+    match req.headers().get("X-Api-Key").and_then(|k| k.to_str().ok()) {
+        Some(k) => Some(k.to_string()),
+        None => web::Query::<KeyParams>::from_query(req.query_string()).ok().and_then(|p| p.into_inner().api_key)
+    }
+}
+
+
+// Paths probed by orchestration that never need a key.
+const PUBLIC_PATHS: [&str; 2] = ["/healthz", "/readyz"];
+
+
+pub fn authorize(req: &ServiceRequest, required: bool, keys: &Keys) -> Result<()> {
+    if PUBLIC_PATHS.contains(&req.path()) {
+        return Ok(());
+    }
+
+    let key = match key(req) {
+        Some(k) => k,
+        None if required => return Err(Error::Unauthorized("An API key is required".to_string())),
+        None => return Ok(())
+    };
+
+    let mut slot = keys.db.borrow_mut();
+    let db = match &mut *slot {
+        Some(db) => db,
+        None => slot.insert(DB::connect()?)
+    };
+    let authorization = api_keys::authorize(db, &key);
+    if authorization.is_err() {
+        *slot = None;
+    }
+
+    match authorization? {
+        Authorization::Allowed => Ok(()),
+        Authorization::Unknown => Err(Error::Unauthorized("Invalid API key".to_string())),
+        Authorization::Exceeded(retry_after) =>
+            Err(Error::TooManyRequests("Hourly request quota exceeded".to_string(), retry_after))
+    }
+}
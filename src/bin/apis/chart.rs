@@ -0,0 +1,116 @@
+use std::error::Error;
+
+use chrono::DateTime;
+use image::{ImageBuffer, ImageOutputFormat, Rgb};
+use plotters::prelude::*;
+use rooney::prices::Price;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 400;
+
+
+#[derive(Clone, Copy)]
+pub enum Currency {
+    Euro,
+    Dollar,
+}
+
+
+impl Currency {
+    pub fn parse(currency: &str) -> Option<Currency> {
+        match currency.to_lowercase().as_str() {
+            "eur" | "euro" => Some(Currency::Euro),
+            "usd" | "dollar" => Some(Currency::Dollar),
+            _ => None
+        }
+    }
+
+    pub fn price(self, price: &Price) -> Decimal {
+        match self {
+            Currency::Euro => price.euro,
+            Currency::Dollar => price.dollar,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Currency::Euro => "€",
+            Currency::Dollar => "$",
+        }
+    }
+}
+
+
+pub fn render(prices: &[Price], currency: Currency, title: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut pixels = vec![0; (WIDTH * HEIGHT * 3) as usize];
+    let points = prices.iter()
+        .filter_map(|p| Some((p.time.and_utc().timestamp(), currency.price(p).to_f32()?)))
+        .collect::<Vec<_>>();
+    let start = points.first().map_or(0, |p| p.0);
+    let end = points.last().map_or(0, |p| p.0).max(start + 1);
+    let min = points.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+    let max = points.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+    let margin = ((max - min) * 0.05).max(max.abs() * 0.001);
+    let colour = match points.last().map(|p| p.1) < points.first().map(|p| p.1) {
+        true => RED,
+        false => GREEN
+    };
+
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 24))
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(80)
+            .build_cartesian_2d(start..end, (min - margin)..(max + margin))?;
+
+        chart.configure_mesh()
+            .x_label_formatter(&|t| format_time(*t, end - start))
+            .y_label_formatter(&|p| format!("{}{:.2}", currency.symbol(), p))
+            .draw()?;
+
+        chart.draw_series(LineSeries::new(points, colour.stroke_width(2)))?;
+        root.present()?;
+    }
+
+    let image: ImageBuffer<Rgb<u8>, _> = ImageBuffer::from_raw(WIDTH, HEIGHT, pixels).ok_or("Invalid image buffer")?;
+    let mut png = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageOutputFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+
+fn format_time(timestamp: i64, span: i64) -> String {
+    let time = DateTime::from_timestamp(timestamp, 0).unwrap_or_default();
+    match span > 2 * 24 * 60 * 60 {
+        true => time.format("%d %b").to_string(),
+        false => time.format("%H:%M").to_string()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn render_png() {
+        let start = NaiveDate::from_ymd_opt(2021, 6, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let prices = (0..288).map(|i| Price {
+            name: "bitcoin".to_string(),
+            ticker: "btc".to_string(),
+            euro: Decimal::new(3_000_000 + (i % 24) * 1_000, 2),
+            dollar: Decimal::new(36_000, 0),
+            time: start + chrono::Duration::minutes(5 * i),
+        }).collect::<Vec<_>>();
+
+        let png = render(&prices, Currency::Euro, "Bitcoin (BTC) Last 24 hours").unwrap();
+        assert_eq!(b"\x89PNG", &png[..4]);
+    }
+}
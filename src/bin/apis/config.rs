@@ -0,0 +1,90 @@
+use std::fs;
+
+use serde::Deserialize;
+
+
+#[derive(Debug, Deserialize)]
+struct File {
+    api: Config,
+}
+
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Config {
+    #[serde(default = "default_address")]
+    pub address: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub tls: Option<Tls>,
+    // Origins sent back in Access-Control-Allow-Origin, "*" allows any origin.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    // Reject requests without a valid key. Keys are checked and counted whenever one is sent.
+    #[serde(default)]
+    pub require_api_key: bool,
+    // Also serves metrics without a key on this address, keep it private as it carries per key counters.
+    pub metrics_address: Option<String>,
+}
+
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Tls {
+    pub cert: String,
+    pub key: String,
+}
+
+
+fn default_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+
+fn default_port() -> u16 {
+    8000
+}
+
+
+fn parse(toml_content: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str::<File>(toml_content).map(|f| f.api)
+}
+
+
+pub fn read_config(path: &str) -> Config {
+    let toml_content = fs::read_to_string(path)
+                        .unwrap_or_else(|_| panic!("Unable to read API config from: {}", path));
+    parse(&toml_content).unwrap_or_else(|e| panic!("Unable to parse TOML from {}: {}", path, e))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_defaults() {
+        let config = parse("[api]").unwrap();
+        assert_eq!(Config { address: "127.0.0.1".to_string(), port: 8000, tls: None, allowed_origins: vec![],
+                            require_api_key: false, metrics_address: None }, config);
+    }
+
+    #[test]
+    fn parse_tls() {
+        let config = parse("[api]
+            address = \"0.0.0.0\"
+            port = 8443
+            allowed_origins = [\"https://example.com\"]
+            require_api_key = true
+            metrics_address = \"127.0.0.1:9102\"
+
+            [api.tls]
+            cert = \"pems/cert.pem\"
+            key = \"pems/key.pem\"").unwrap();
+
+        assert_eq!("0.0.0.0", config.address);
+        assert_eq!(8443, config.port);
+        assert_eq!(vec!["https://example.com".to_string()], config.allowed_origins);
+        assert!(config.require_api_key);
+        assert_eq!(Some("127.0.0.1:9102".to_string()), config.metrics_address);
+        assert_eq!(Some(Tls { cert: "pems/cert.pem".to_string(), key: "pems/key.pem".to_string() }), config.tls);
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use log::error;
+use serde::Serialize;
+
+
+#[derive(Debug)]
+pub enum Error {
+    BadRequest(String),
+    Unauthorized(String),
+    NotFound(String),
+    // Message and the number of seconds until the client may retry.
+    TooManyRequests(String, i64),
+    Internal(String),
+    Unavailable(String),
+}
+
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+
+impl Error {
+    fn message(&self) -> &str {
+        match self {
+            Error::BadRequest(m) | Error::Unauthorized(m) | Error::NotFound(m) | Error::Internal(m)
+                | Error::Unavailable(m) | Error::TooManyRequests(m, _) => m,
+        }
+    }
+}
+
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::TooManyRequests(_, _) => StatusCode::TOO_MANY_REQUESTS,
+            Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let Error::TooManyRequests(_, retry_after) = self {
+            response.header("Retry-After", retry_after.to_string());
+        }
+        response.json(ErrorBody { error: self.message() })
+    }
+}
+
+
+impl From<postgres::Error> for Error {
+    fn from(e: postgres::Error) -> Error {
+        error!("Database error: {}", e);
+        match e.code() {
+            Some(_) => Error::Internal("Database query failed".to_string()),
+            None => Error::Unavailable("Database unavailable".to_string())
+        }
+    }
+}
+
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
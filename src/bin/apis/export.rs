@@ -0,0 +1,68 @@
+use actix_web::{get, web, HttpResponse};
+use chrono::{Duration, NaiveDate, Utc};
+use rooney::export::{self, Format, Table};
+use serde::Deserialize;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::{connect, get_coin};
+
+
+const MAX_COINS: usize = 20;
+const DEFAULT_DAYS: i64 = 7;
+// Exports are built in memory so the range is bounded, more so for five minute prices than for daily stats.
+const MAX_PRICE_DAYS: i64 = 31;
+const MAX_STATS_DAYS: i64 = 10 * 366;
+
+
+#[derive(Deserialize)]
+struct ExportParams {
+    coins: String,
+    from: Option<String>,
+    to: Option<String>,
+    format: Option<String>,
+}
+
+
+fn parse_date(date: &Option<String>, name: &str) -> Result<Option<NaiveDate>> {
+    date.as_ref().map(|d| NaiveDate::from_str(d)
+        .map_err(|_| Error::BadRequest(format!("Invalid {}: {}, expected YYYY-MM-DD", name, d)))).transpose()
+}
+
+
+#[get("/export/{table}")]
+async fn get_export(table: web::Path<String>, params: web::Query<ExportParams>) -> Result<HttpResponse> {
+    let table = Table::parse(&table).ok_or_else(|| Error::NotFound(format!("Unknown table: {}", table)))?;
+    let format = params.format.as_deref().unwrap_or("csv");
+    let format = Format::parse(format).ok_or_else(|| Error::BadRequest("format must be csv or parquet".to_string()))?;
+    let to = parse_date(&params.to, "to")?.unwrap_or_else(|| Utc::now().date_naive());
+    let from = parse_date(&params.from, "from")?.unwrap_or(to - Duration::days(DEFAULT_DAYS - 1));
+    let max_days = match table {
+        Table::Prices => MAX_PRICE_DAYS,
+        Table::DailyStats => MAX_STATS_DAYS,
+    };
+    if from > to || (to - from).num_days() >= max_days {
+        return Err(Error::BadRequest(format!("from must be before to and at most {} days earlier", max_days - 1)));
+    }
+
+    let mut db = connect()?;
+    let mut coins = Vec::new();
+    for c in params.coins.split(',').filter(|c| !c.is_empty()) {
+        let coin = get_coin(&mut db, c.to_string())?;
+        if !coins.contains(&coin) {
+            coins.push(coin);
+        }
+    }
+    if coins.is_empty() || coins.len() > MAX_COINS {
+        return Err(Error::BadRequest(format!("coins must list between 1 and {} coins", MAX_COINS)));
+    }
+
+    let mut body = Vec::new();
+    export::write(&mut db, table, format, &coins, from, to, &mut body).map_err(|e| Error::Internal(e.to_string()))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .header("Content-Disposition", format!("attachment; filename=\"{}_{}_{}.{}\"", table.name(), from, to,
+                                               format.extension()))
+        .body(body))
+}
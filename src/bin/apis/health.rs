@@ -0,0 +1,57 @@
+use actix_web::{get, web, HttpResponse};
+use log::error;
+use rooney::prices;
+use serde::Serialize;
+
+use crate::connect;
+
+
+pub struct Staleness {
+    pub seconds: i64,
+}
+
+
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+    database: &'static str,
+    price_age_seconds: Option<i64>,
+    stale_after_seconds: i64,
+}
+
+
+fn check(staleness: &Staleness) -> Health {
+    let age = connect().and_then(|mut db| Ok(prices::age(&mut db)?));
+    let (database, price_age_seconds) = match age {
+        Ok(a) => ("ok", a),
+        Err(e) => {
+            error!("Health check failed: {}", e);
+            ("unavailable", None)
+        }
+    };
+
+    let status = match (database, price_age_seconds) {
+        ("ok", Some(a)) if a <= staleness.seconds => "ok",
+        ("ok", _) => "stale",
+        _ => "unavailable"
+    };
+
+    Health { status, database, price_age_seconds, stale_after_seconds: staleness.seconds }
+}
+
+
+// Liveness only depends on the process so a database outage doesn't get the API restarted.
+#[get("/healthz")]
+async fn get_health(staleness: web::Data<Staleness>) -> HttpResponse {
+    HttpResponse::Ok().json(check(&staleness))
+}
+
+
+#[get("/readyz")]
+async fn get_ready(staleness: web::Data<Staleness>) -> HttpResponse {
+    let health = check(&staleness);
+    match health.status {
+        "ok" => HttpResponse::Ok().json(health),
+        _ => HttpResponse::ServiceUnavailable().json(health)
+    }
+}
@@ -1,110 +1,310 @@
-use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
-use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+mod auth;
+mod chart;
+mod config;
+mod error;
+mod export;
+mod health;
+mod metrics;
+mod stream;
+mod v1;
+
+use actix_cors::Cors;
+use actix_web::{dev::Service, get, http, web, App, HttpResponse, HttpServer};
+use futures::future::{ready, Either, FutureExt};
+use error::{Error, Result};
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod};
 use rooney::db;
+use rooney::candles::{self, Interval};
+use rooney::prices::{self, Price, Summary};
+use rooney::window::Window;
+use rust_decimal::Decimal;
+use titlecase::titlecase;
 
+use chrono::{NaiveDate, NaiveDateTime};
 use log::{error, info};
-use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Instant;
+
 
+fn connect() -> Result<db::DB> {
+    db::DB::new().map_err(Error::from)
+}
 
-fn get_coin(db: &mut db::DB, coin: String) -> String {
+
+fn get_coin(db: &mut db::DB, coin: String) -> Result<String> {
+    let coin = coin.to_lowercase();
     if db.all_coins.contains(&coin) {
-        return coin;
+        return Ok(coin);
     }
 
     match db.nicks_coins.get(&coin) {
-        Some(c) => c,
-        None => "bitcoin"
-    }.to_string()
+        Some(c) => Ok(c.to_string()),
+        None => Err(Error::NotFound(format!("Unknown coin: {}", coin)))
+    }
+}
+
+
+pub fn query(db: &mut db::DB, coin: &str) -> Result<Vec<Price>> {
+    let prices = prices::query(db, coin, "microseconds", "24 hours")?;
+    if prices.is_empty() {
+        return Err(Error::NotFound(format!("No prices for {} in the last 24 hours", coin)));
+    }
+
+    Ok(prices)
+}
+
+
+fn json<T: Serialize>(value: &T) -> Result<HttpResponse> {
+    let j = serde_json::to_string(value).map_err(|e| Error::Internal(e.to_string()))?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(j))
+}
+
+
+#[get("/prices/{coin}")]
+async fn get_prices_last_24_hours(coin: web::Path<String>) -> Result<HttpResponse> {
+    let coin = coin.into_inner();
+    let mut db = connect()?;
+    let c = get_coin(&mut db, coin)?;
+    json(&query(&mut db, &c)?)
+}
+
+
+#[derive(Deserialize)]
+struct ChartParams {
+    range: Option<String>,
+    currency: Option<String>,
+}
+
+
+fn parse_currency(currency: &Option<String>) -> Result<chart::Currency> {
+    match currency.as_deref() {
+        None => Ok(chart::Currency::Euro),
+        Some(c) => chart::Currency::parse(c).ok_or_else(|| Error::BadRequest("currency must be eur or usd".to_string()))
+    }
+}
+
+
+#[get("/chart/{coin}.png")]
+async fn get_chart(coin: web::Path<String>, params: web::Query<ChartParams>) -> Result<HttpResponse> {
+    let window = match params.range.as_deref() {
+        None => Window::last_24_hours(),
+        Some(r) => Window::parse(r).ok_or_else(|| Error::BadRequest(format!("Invalid range: {}", r)))?
+    };
+    let currency = parse_currency(&params.currency)?;
+
+    let coin = coin.into_inner();
+    let mut db = connect()?;
+    let c = get_coin(&mut db, coin)?;
+    let prices = prices::query(&mut db, &c, window.unit, &window.interval)?;
+    let title = match prices.first() {
+        Some(p) => format!("{} ({}) {}", titlecase(&p.name), p.ticker.to_uppercase(), window.long),
+        None => return Err(Error::NotFound(format!("No prices for {} in range {}", c, window.arg)))
+    };
+
+    match chart::render(&prices, currency, &title) {
+        Ok(png) => Ok(HttpResponse::Ok()
+            .content_type("image/png")
+            .body(png)),
+        Err(e) => {
+            error!("Unable to render chart for {}: {}", c, e);
+            Err(Error::Internal("Unable to render chart".to_string()))
+        }
+    }
+}
+
+
+#[derive(Deserialize)]
+struct CandleParams {
+    interval: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    currency: Option<String>,
 }
 
 
-#[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize)]
-struct Prices {
-    Prices: Vec<Price>
+fn parse_time(time: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::from_str(time).ok()
+        .or_else(|| NaiveDate::from_str(time).ok().and_then(|d| d.and_hms_opt(0, 0, 0)))
 }
 
 
-#[allow(non_snake_case)]
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Price {
+fn parse_optional_time(time: &Option<String>, name: &str) -> Result<Option<NaiveDateTime>> {
+    match time.as_deref() {
+        None => Ok(None),
+        Some(t) => parse_time(t).map(Some).ok_or_else(|| Error::BadRequest(format!("{} must be a date or timestamp", name)))
+    }
+}
+
+
+#[get("/candles/{coin}")]
+async fn get_candles(coin: web::Path<String>, params: web::Query<CandleParams>) -> Result<HttpResponse> {
+    let interval = match params.interval.as_deref() {
+        None => Interval::Hour,
+        Some(i) => Interval::parse(i).ok_or_else(|| Error::BadRequest("interval must be one of 15m, 1h, 4h or 1d".to_string()))?
+    };
+    let from = parse_optional_time(&params.from, "from")?;
+    let to = parse_optional_time(&params.to, "to")?;
+    let dollar = matches!(parse_currency(&params.currency)?, chart::Currency::Dollar);
+
+    let coin = coin.into_inner();
+    let mut db = connect()?;
+    let c = get_coin(&mut db, coin)?;
+    json(&candles::query(&mut db, &c, interval, from, to, dollar)?)
+}
+
+
+#[derive(Serialize)]
+struct LastPrice {
     name: String,
     ticker: String,
-    euro: f32,
-    dollar: f32,
+    euro: Decimal,
+    dollar: Decimal,
     time: NaiveDateTime,
+    age_seconds: i64,
+    today: Option<Change>,
+    last_24_hours: Change,
 }
 
 
-pub fn query(db: &mut db::DB, coin: &str) -> Option<Vec<Price>> {
-    let query =
-        "select name, ticker, cast(euro as real), cast(dollar as real), time
-        from prices
-        join coins using(coin_id)
-        where time >= now() - interval '24 hours'
-        and name = $1
-        order by time asc";
+#[derive(Serialize)]
+struct Change {
+    change: f32,
+    min: Decimal,
+    max: Decimal,
+    median: Decimal,
+}
 
-    let rows = db.connection.query(query, &[&coin]).unwrap();
-    if rows.is_empty() {
-        return None;
-    }
 
-    Some(rows
-        .iter()
-        .map(|row| Price {
-                name: row.get(0),
-                ticker: row.get(1),
-                euro: row.get(2),
-                dollar: row.get(3),
-                time: row.get(4),
-            }
-        )
-        .collect())
+impl LastPrice {
+    fn new(last_24_hours: Summary, today: Option<Summary>) -> Self {
+        Self {
+            today: today.map(|t| Change::new(&t)),
+            last_24_hours: Change::new(&last_24_hours),
+            name: last_24_hours.name,
+            ticker: last_24_hours.ticker,
+            euro: last_24_hours.euro,
+            dollar: last_24_hours.dollar,
+            time: last_24_hours.time,
+            age_seconds: last_24_hours.age,
+        }
+    }
 }
 
 
-#[get("/prices/{coin}")]
-async fn get_prices_last_24_hours(coin: web::Path<String>) -> impl Responder {
-    let coin = coin.into_inner();
-    let mut db = db::DB::new().expect("Unable to access DB");
-    let c = get_coin(&mut db, coin.to_string());
-    let prices = query(&mut db, &c).unwrap();
-    let j = serde_json::to_string(&prices).unwrap();
-    HttpResponse::Ok()
-        .header("Access-Control-Allow-Origin", "*")
-        .body(j)
+impl Change {
+    fn new(summary: &Summary) -> Self {
+        Self {
+            change: summary.change,
+            min: summary.min,
+            max: summary.max,
+            median: summary.median,
+        }
+    }
 }
 
 
 #[get("/coin/{coin}")]
-async fn get_last_price(coin: web::Path<String>) -> impl Responder {
+async fn get_last_price(coin: web::Path<String>) -> Result<HttpResponse> {
     let coin = coin.into_inner();
-    let mut db = db::DB::new().expect("Unable to access DB");
-    let c = get_coin(&mut db, coin.to_string());
-    HttpResponse::Ok()
-        .header("Access-Control-Allow-Origin", "*")
-        .body(format!("Most recent price {}\n", c))
+    let mut db = connect()?;
+    let coins = [get_coin(&mut db, coin)?];
+    let today = Window::today();
+    let day = Window::last_24_hours();
+    let today = prices::summaries(&mut db, &coins, today.unit, &today.interval)?.pop();
+    let last_24_hours = match prices::summaries(&mut db, &coins, day.unit, &day.interval)?.pop() {
+        Some(s) => s,
+        None => return Err(Error::NotFound(format!("No prices for {} in the last 24 hours", coins[0])))
+    };
+
+    json(&LastPrice::new(last_24_hours, today))
+}
+
+
+async fn not_found() -> Result<HttpResponse> {
+    Err(Error::NotFound("No such endpoint".to_string()))
+}
+
+
+fn cors(allowed_origins: &[String]) -> Cors {
+    let cors = Cors::default()
+        .allowed_methods(vec![http::Method::GET])
+        .max_age(3600);
+
+    match allowed_origins.iter().any(|o| o == "*") {
+        true => cors.allow_any_origin().send_wildcard(),
+        false => allowed_origins.iter().fold(cors, |c, o| c.allowed_origin(o))
+    }
+}
+
+
+fn ssl_acceptor(tls: &config::Tls) -> std::io::Result<SslAcceptorBuilder> {
+    let error = |path: &str, e| std::io::Error::other(format!("Unable to load {}: {}", path, e));
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder.set_private_key_file(&tls.key, SslFiletype::PEM).map_err(|e| error(&tls.key, e))?;
+    builder.set_certificate_chain_file(&tls.cert).map_err(|e| error(&tls.cert, e))?;
+    Ok(builder)
 }
 
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    info!("Launching Actix");
-
-    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
-    builder
-        .set_private_key_file("pems/key.pem", SslFiletype::PEM)
-        .unwrap();
-    builder.set_certificate_chain_file("pems/cert.pem").unwrap();
+    env_logger::init();
+    let config = config::read_config("configuration/Api.toml");
+    if let Some(address) = &config.metrics_address {
+        rooney::metrics::serve(address)?;
+    }
+    let address = (config.address.as_str(), config.port);
+    let allowed_origins = config.allowed_origins.clone();
+    let require_api_key = config.require_api_key;
+    let hub = web::Data::new(stream::Hub::start());
+    let staleness = web::Data::new(health::Staleness { seconds: *prices::STALE_AFTER_SECONDS });
 
-    HttpServer::new(|| {
+    let server = HttpServer::new(move || {
+        let keys = Rc::new(auth::Keys::default());
         App::new()
+            .wrap_fn(move |req, srv| match auth::authorize(&req, require_api_key, &keys) {
+                Ok(()) => Either::Left(srv.call(req)),
+                Err(e) => Either::Right(ready(Ok(req.error_response(e))))
+            })
+            .wrap_fn(|req, srv| {
+                let start = Instant::now();
+                srv.call(req).map(move |response| {
+                    if let Ok(r) = &response {
+                        metrics::record(r, start);
+                    }
+                    response
+                })
+            })
+            .wrap(cors(&allowed_origins))
+            .app_data(hub.clone())
+            .app_data(staleness.clone())
+            .app_data(web::PathConfig::default().error_handler(|e, _| Error::BadRequest(e.to_string()).into()))
+            .app_data(web::QueryConfig::default().error_handler(|e, _| Error::BadRequest(e.to_string()).into()))
             .service(get_prices_last_24_hours)
             .service(get_last_price)
-    })
-        .bind_openssl("0.0.0.0:8000", builder)?
-        .run()
-        .await
+            .service(get_chart)
+            .service(get_candles)
+            .service(metrics::get_metrics)
+            .service(health::get_health)
+            .service(health::get_ready)
+            .service(web::scope("/v1").configure(v1::configure))
+            .default_service(web::route().to(not_found))
+    });
+
+    let server = match &config.tls {
+        Some(tls) => {
+            info!("Listening on https://{}:{}", config.address, config.port);
+            server.bind_openssl(address, ssl_acceptor(tls)?)?
+        },
+        None => {
+            info!("Listening on http://{}:{}", config.address, config.port);
+            server.bind(address)?
+        }
+    };
+
+    server.run().await
 }
@@ -0,0 +1,32 @@
+use std::time::Instant;
+
+use actix_web::{dev::ServiceResponse, get, HttpResponse};
+use lazy_static::lazy_static;
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+use rooney::metrics;
+
+
+lazy_static! {
+    static ref REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "rooney_api_requests_total", "HTTP requests by route and status.", &["route", "status"]
+    ).unwrap();
+    static ref REQUEST_SECONDS: HistogramVec = register_histogram_vec!(
+        "rooney_api_request_duration_seconds", "Time taken to respond to HTTP requests.", &["route"]
+    ).unwrap();
+}
+
+
+// Labels by route pattern rather than path so coin names don't create a series each.
+pub fn record<B>(response: &ServiceResponse<B>, start: Instant) {
+    let route = response.request().match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    REQUESTS.with_label_values(&[&route, response.status().as_str()]).inc();
+    REQUEST_SECONDS.with_label_values(&[&route]).observe(start.elapsed().as_secs_f64());
+}
+
+
+#[get("/metrics")]
+async fn get_metrics() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render())
+}
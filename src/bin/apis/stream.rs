@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::{thread, time};
+
+use actix_web::{get, web, HttpResponse};
+use actix_web::web::Bytes;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::StreamExt;
+use log::{error, info};
+use postgres::fallible_iterator::FallibleIterator;
+use rooney::db;
+use rooney::prices::{self, Price};
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::{connect, get_coin};
+
+
+const MAX_COINS: usize = 20;
+const KEEP_ALIVE: time::Duration = time::Duration::from_secs(30);
+const RECONNECT: time::Duration = time::Duration::from_secs(10);
+
+
+struct Subscriber {
+    coins: HashSet<String>,
+    sender: UnboundedSender<Bytes>,
+}
+
+
+// Fans out prices announced by the updater to every connected stream. A single listener thread holds the
+// only database connection so the number of clients doesn't affect the load on Postgres.
+#[derive(Default)]
+pub struct Hub {
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+
+impl Hub {
+    pub fn start() -> Arc<Hub> {
+        let hub = Arc::new(Hub::default());
+        let listener = hub.clone();
+        thread::spawn(move || loop {
+            if let Err(e) = listener.listen() {
+                error!("Price listener failed: {}", e);
+            }
+            thread::sleep(RECONNECT);
+        });
+
+        hub
+    }
+
+    fn listen(&self) -> std::result::Result<(), postgres::Error> {
+        let mut db = db::DB::new()?;
+        db.batch_execute("stream_listen", &format!("Listen {}", prices::CHANNEL))?;
+        info!("Listening for price updates");
+
+        loop {
+            let notified = db.connection.notifications().timeout_iter(KEEP_ALIVE).next()?.is_some();
+            let coins = self.coins();
+            if notified && !coins.is_empty() {
+                self.publish(&prices::latest(&mut db, &coins)?);
+            } else {
+                self.send_all(|_| Some(Bytes::from_static(b": keep-alive\n\n")));
+            }
+        }
+    }
+
+    fn subscribe(&self, coins: HashSet<String>) -> UnboundedReceiver<Bytes> {
+        let (sender, receiver) = unbounded();
+        self.subscribers.lock().unwrap().push(Subscriber { coins, sender });
+        receiver
+    }
+
+    fn coins(&self) -> Vec<String> {
+        let subscribers = self.subscribers.lock().unwrap();
+        subscribers.iter().flat_map(|s| s.coins.iter().cloned()).collect::<HashSet<_>>().into_iter().collect()
+    }
+
+    fn publish(&self, prices: &[Price]) {
+        let events = prices.iter().map(|p| (p.name.as_str(), event(p))).collect::<Vec<_>>();
+        self.send_all(|s| {
+            let events = events.iter().filter(|e| s.coins.contains(e.0)).map(|e| e.1.as_str()).collect::<String>();
+            match events.is_empty() {
+                true => None,
+                false => Some(Bytes::from(events))
+            }
+        });
+    }
+
+    // Subscribers whose client has gone away are dropped on the next send.
+    fn send_all(&self, message: impl Fn(&Subscriber) -> Option<Bytes>) {
+        self.subscribers.lock().unwrap().retain(|s| match message(s) {
+            Some(m) => s.sender.unbounded_send(m).is_ok(),
+            None => !s.sender.is_closed()
+        });
+    }
+}
+
+
+fn event(price: &Price) -> String {
+    format!("event: price\ndata: {}\n\n", serde_json::to_string(price).unwrap_or_default())
+}
+
+
+#[derive(Deserialize)]
+struct StreamParams {
+    coins: String,
+}
+
+
+#[get("/stream")]
+async fn get_stream(hub: web::Data<Arc<Hub>>, params: web::Query<StreamParams>) -> Result<HttpResponse> {
+    let mut db = connect()?;
+    let coins = params.coins.split(',')
+        .filter(|c| !c.is_empty())
+        .map(|c| get_coin(&mut db, c.to_string()))
+        .collect::<Result<HashSet<_>>>()?;
+    if coins.is_empty() || coins.len() > MAX_COINS {
+        return Err(Error::BadRequest(format!("coins must list between 1 and {} coins", MAX_COINS)));
+    }
+
+    let latest = prices::latest(&mut db, &coins.iter().cloned().collect::<Vec<_>>())?;
+    let receiver = hub.subscribe(coins);
+    let initial = futures::stream::iter(latest.iter().map(|p| Bytes::from(event(p))).collect::<Vec<_>>());
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(initial.chain(receiver).map(Ok::<_, actix_web::Error>)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn price(name: &str) -> Price {
+        Price {
+            name: name.to_string(),
+            ticker: name[..3].to_string(),
+            euro: Decimal::ONE,
+            dollar: Decimal::new(15, 1),
+            time: NaiveDate::from_ymd_opt(2021, 6, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn publish_subscribed_coins() {
+        let hub = Hub::default();
+        let mut receiver = hub.subscribe(vec!["bitcoin".to_string()].into_iter().collect());
+        hub.publish(&[price("bitcoin"), price("ethereum")]);
+
+        let message = receiver.try_recv().unwrap();
+        assert_eq!(event(&price("bitcoin")), String::from_utf8(message.to_vec()).unwrap());
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn drop_closed_subscribers() {
+        let hub = Hub::default();
+        drop(hub.subscribe(vec!["bitcoin".to_string()].into_iter().collect()));
+        hub.publish(&[price("bitcoin")]);
+        assert!(hub.coins().is_empty());
+    }
+}
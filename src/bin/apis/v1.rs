@@ -0,0 +1,209 @@
+use actix_web::{get, web, HttpResponse};
+use chrono::NaiveDate;
+use rooney::candles::Interval;
+use rooney::window::Window;
+use rooney::{ats, coins, movers, prices, stats};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::{connect, get_coin, json, parse_currency, parse_optional_time};
+
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+const OPENAPI: &str = include_str!("openapi.json");
+
+
+#[derive(Deserialize)]
+struct PageParams {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+
+#[derive(Serialize)]
+struct Page<T> {
+    data: Vec<T>,
+    limit: i64,
+    offset: i64,
+    next_offset: Option<i64>,
+}
+
+
+impl PageParams {
+    fn parse(&self) -> Result<(i64, i64)> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        let offset = self.offset.unwrap_or(0);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(Error::BadRequest(format!("limit must be between 1 and {}", MAX_LIMIT)));
+        }
+        if offset < 0 {
+            return Err(Error::BadRequest("offset must not be negative".to_string()));
+        }
+
+        Ok((limit, offset))
+    }
+}
+
+
+impl<T> Page<T> {
+    // Queries fetch one row more than the limit so the next page can be detected without a count.
+    fn new(mut data: Vec<T>, limit: i64, offset: i64) -> Self {
+        let next_offset = match data.len() as i64 > limit {
+            true => Some(offset + limit),
+            false => None
+        };
+        data.truncate(limit as usize);
+
+        Self { data, limit, offset, next_offset }
+    }
+}
+
+
+#[get("/coins")]
+async fn get_coins(page: web::Query<PageParams>) -> Result<HttpResponse> {
+    let (limit, offset) = page.parse()?;
+    let mut db = connect()?;
+    json(&Page::new(coins::list(&mut db, limit + 1, offset)?, limit, offset))
+}
+
+
+#[derive(Deserialize)]
+struct PriceParams {
+    from: Option<String>,
+    to: Option<String>,
+    interval: Option<String>,
+    currency: Option<String>,
+}
+
+
+#[derive(Serialize)]
+struct PricePoint {
+    time: chrono::NaiveDateTime,
+    price: Decimal,
+}
+
+
+#[get("/coins/{id}/prices")]
+async fn get_prices(id: web::Path<String>, params: web::Query<PriceParams>, page: web::Query<PageParams>)
+                    -> Result<HttpResponse> {
+    let (limit, offset) = page.parse()?;
+    let from = parse_optional_time(&params.from, "from")?;
+    let to = parse_optional_time(&params.to, "to")?;
+    if let (Some(f), Some(t)) = (from, to) {
+        if f >= t {
+            return Err(Error::BadRequest("from must be before to".to_string()));
+        }
+    }
+    let bucket = match params.interval.as_deref() {
+        None => None,
+        Some(i) => Some(Interval::parse(i).map(Interval::seconds)
+            .ok_or_else(|| Error::BadRequest("interval must be one of 15m, 1h, 4h or 1d".to_string()))?)
+    };
+    let currency = parse_currency(&params.currency)?;
+
+    let mut db = connect()?;
+    let coin = get_coin(&mut db, id.into_inner())?;
+    let prices = prices::history(&mut db, &coin, from, to, bucket, limit + 1, offset)?
+        .iter()
+        .map(|p| PricePoint { time: p.time, price: currency.price(p) })
+        .collect();
+
+    json(&Page::new(prices, limit, offset))
+}
+
+
+#[get("/coins/{id}/stats/{date}")]
+async fn get_stats(path: web::Path<(String, String)>) -> Result<HttpResponse> {
+    let (id, date) = path.into_inner();
+    let date = NaiveDate::from_str(&date)
+        .map_err(|_| Error::BadRequest(format!("Invalid date: {}, expected YYYY-MM-DD", date)))?;
+
+    let mut db = connect()?;
+    let coin = get_coin(&mut db, id)?;
+    match stats::query(&mut db, std::slice::from_ref(&coin), date)?.pop() {
+        Some(s) => json(&s),
+        None => Err(Error::NotFound(format!("No stats for {} on {}", coin, date)))
+    }
+}
+
+
+#[get("/coins/{id}/ats")]
+async fn get_ats(id: web::Path<String>) -> Result<HttpResponse> {
+    let mut db = connect()?;
+    let coin = get_coin(&mut db, id.into_inner())?;
+    match ats::query(&mut db, &coin)? {
+        Some(a) => json(&a),
+        None => Err(Error::NotFound(format!("No prices for {}", coin)))
+    }
+}
+
+
+#[derive(Deserialize)]
+struct MoverParams {
+    window: Option<String>,
+    direction: Option<String>,
+}
+
+
+#[get("/movers")]
+async fn get_movers(params: web::Query<MoverParams>, page: web::Query<PageParams>) -> Result<HttpResponse> {
+    let (limit, offset) = page.parse()?;
+    let window = match params.window.as_deref() {
+        None => Window::today(),
+        Some(w) => Window::parse(w).ok_or_else(|| Error::BadRequest(format!("Invalid window: {}", w)))?
+    };
+    let direction = match params.direction.as_deref() {
+        None | Some("up") => 1.0,
+        Some("down") => -1.0,
+        Some(_) => return Err(Error::BadRequest("direction must be up or down".to_string()))
+    };
+
+    let mut db = connect()?;
+    let movers = movers::query(&mut db, window.unit, &window.interval, direction, limit + 1, offset)?;
+    json(&Page::new(movers, limit, offset))
+}
+
+
+#[get("/openapi.json")]
+async fn get_openapi() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(OPENAPI)
+}
+
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_coins)
+        .service(get_prices)
+        .service(get_stats)
+        .service(get_ats)
+        .service(get_movers)
+        .service(crate::stream::get_stream)
+        .service(crate::export::get_export)
+        .service(get_openapi);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_next_offset() {
+        let page = Page::new(vec![1, 2, 3], 2, 4);
+        assert_eq!(vec![1, 2], page.data);
+        assert_eq!(Some(6), page.next_offset);
+        assert_eq!(None, Page::new(vec![1, 2], 2, 0).next_offset);
+    }
+
+    #[test]
+    fn openapi_documents_routes() {
+        let document: serde_json::Value = serde_json::from_str(OPENAPI).unwrap();
+        for path in ["/coins", "/coins/{id}/prices", "/coins/{id}/stats/{date}", "/coins/{id}/ats", "/movers", "/stream", "/export/{table}"] {
+            assert!(document["paths"].get(path).is_some(), "{} is not documented", path);
+        }
+    }
+}
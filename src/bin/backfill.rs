@@ -0,0 +1,302 @@
+use std::{env, error::Error, fs, path::Path, process};
+
+use chrono::{DateTime, NaiveDate};
+use postgres::binary_copy::BinaryCopyInWriter;
+use postgres::types::Type;
+use rooney::db;
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+
+const USAGE: &str = "Usage: backfill [--overwrite] <coin|ticker> <file.csv|file.json>...
+Imports daily euro prices, such as CoinGecko exports, into daily_stats. Days already in daily_stats are kept
+and reported when their averages differ by more than 5%. --overwrite replaces those averages and medians, lows and
+highs are only ever widened.";
+const MAX_CONFLICTS_SHOWN: usize = 20;
+// Exports hold a single price a day, so they only conflict with the updater's daily average when well apart from it.
+const CONFLICT_TOLERANCE_PERCENT: i64 = 5;
+
+
+// A daily price from an export, always in euro.
+type Point = (NaiveDate, Decimal);
+
+
+struct Conflict {
+    date: NaiveDate,
+    existing: Decimal,
+    imported: Decimal,
+}
+
+
+struct Import {
+    inserted: u64,
+    unchanged: usize,
+    conflicts: Vec<Conflict>,
+}
+
+
+fn conflicts(existing: Decimal, imported: Decimal) -> bool {
+    (existing - imported).abs() * Decimal::from(100) > existing.abs() * Decimal::from(CONFLICT_TOLERANCE_PERCENT)
+}
+
+
+// Dates come as 2021-06-01, 2021-06-01 00:00:00 UTC, RFC 3339 or a unix timestamp in seconds or milliseconds.
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    if let Ok(timestamp) = value.parse::<i64>() {
+        let seconds = if timestamp.abs() > 100_000_000_000 { timestamp / 1000 } else { timestamp };
+        return DateTime::from_timestamp(seconds, 0).map(|t| t.date_naive());
+    }
+
+    value.get(..10).and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+}
+
+
+// CSV with a header naming the date column (snapped_at, date or time) and the price column (price or close).
+fn parse_csv(content: &str) -> Result<Vec<Point>, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.iter().map(|h| h.trim().to_lowercase()).collect::<Vec<_>>();
+    let column = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+    let date = column(&["snapped_at", "date", "time"]).ok_or("No snapped_at, date or time column")?;
+    let price = column(&["price", "close"]).ok_or("No price or close column")?;
+
+    let mut points = Vec::new();
+    for (line, record) in reader.records().enumerate() {
+        let record = record?;
+        let point = record.get(date).and_then(parse_date)
+            .zip(record.get(price).and_then(|p| p.trim().parse::<Decimal>().ok()).filter(|p| *p > Decimal::ZERO));
+        points.push(point.ok_or_else(|| format!("Invalid row {}: {:?}", line + 2, record))?);
+    }
+
+    Ok(points)
+}
+
+
+fn json_point(value: &Value) -> Option<Point> {
+    let (date, price) = match value {
+        Value::Array(pair) if pair.len() == 2 => (&pair[0], &pair[1]),
+        Value::Object(o) => (["snapped_at", "date", "time"].iter().find_map(|k| o.get(*k))?,
+                             ["price", "close"].iter().find_map(|k| o.get(*k))?),
+        _ => return None
+    };
+    let date = match date {
+        Value::String(d) => parse_date(d),
+        d => parse_date(&d.to_string())
+    }?;
+
+    Some((date, serde_json::from_value(price.clone()).ok()?))
+}
+
+
+// CoinGecko's market_chart response ({"prices": [[ms, price], ...]}) or a list of objects with the CSV's fields.
+fn parse_json(content: &str) -> Result<Vec<Point>, Box<dyn Error>> {
+    let json: Value = serde_json::from_str(content)?;
+    let rows = match &json {
+        Value::Object(o) => o.get("prices").and_then(|p| p.as_array()).ok_or("No prices array")?,
+        Value::Array(a) => a,
+        _ => return Err("Expected an object or an array".into())
+    };
+
+    rows.iter().enumerate()
+        .map(|(i, r)| json_point(r).filter(|p| p.1 > Decimal::ZERO).ok_or_else(|| format!("Invalid entry {}: {}", i, r).into()))
+        .collect()
+}
+
+
+fn read(path: &str) -> Result<Vec<Point>, Box<dyn Error>> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let points = match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("json") => parse_json(&content),
+        _ => parse_csv(&content)
+    };
+
+    points.map_err(|e| format!("Unable to parse {}: {}", path, e).into())
+}
+
+
+// Accepts a name, a ticker or a CoinGecko id such as bitcoin-cash.
+fn find_coin(db: &mut db::DB, coin: &str) -> Result<(i32, String), Box<dyn Error>> {
+    let coin = coin.to_lowercase();
+    let name = [coin.clone(), coin.replace('-', " ")].iter()
+        .find(|c| db.all_coins.contains(*c)).cloned()
+        .or_else(|| db.nicks_coins.get(&coin).cloned())
+        .ok_or_else(|| format!("Unknown coin: {}", coin))?;
+
+    let rows = db.query("backfill_coin", "select coin_id from coins where name = $1", &[&name])?;
+    let coin_id = rows.first().map(|r| r.get(0)).ok_or_else(|| format!("Unknown coin: {}", name))?;
+    Ok((coin_id, name))
+}
+
+
+// Days are aggregated in case an export has more than one price a day. Today is left to the updater.
+fn import(db: &mut db::DB, coin_id: i32, points: &[Point], overwrite: bool) -> Result<Import, Box<dyn Error>> {
+    db.transaction("backfill_import", |transaction| {
+        transaction.batch_execute("Create temporary table temp_history(date date, price numeric) on commit drop")?;
+
+        let sink = transaction.copy_in("Copy temp_history(date, price) from stdin (format binary)")?;
+        let mut writer = BinaryCopyInWriter::new(sink, &[Type::DATE, Type::NUMERIC]);
+        for (date, price) in points {
+            writer.write(&[date, price])?;
+        }
+        writer.finish()?;
+
+        // The median averages the middle two prices in numeric, percentile_cont would round it through a float.
+        transaction.batch_execute(
+            "Create temporary table temp_daily on commit drop as
+            select date, min(price) as min_euro, avg(price) as average_euro,
+            ((array_agg(price order by price))[(count(*)::int + 1) / 2]
+             + (array_agg(price order by price))[(count(*)::int + 2) / 2]) / 2 as median_euro,
+            coalesce(stddev_pop(price), 0) as std_dev, max(price) as max_euro
+            from temp_history
+            where date < current_date
+            group by date")?;
+
+        let (conflicting, unchanged): (Vec<_>, Vec<_>) = transaction.query(
+            "Select t.date, d.average_euro, t.average_euro from temp_daily t
+            join daily_stats d on d.coin_id = $1 and d.date = t.date
+            order by t.date", &[&coin_id])?
+            .iter()
+            .map(|r| Conflict { date: r.get(0), existing: r.get(1), imported: r.get(2) })
+            .partition(|c| conflicts(c.existing, c.imported));
+
+        // The standard deviation is kept as a single daily price says nothing about the day's spread.
+        if overwrite {
+            let dates = conflicting.iter().map(|c| c.date).collect::<Vec<_>>();
+            transaction.execute(
+                "Update daily_stats d set average_euro = t.average_euro, median_euro = t.median_euro,
+                min_euro = least(d.min_euro, t.min_euro), max_euro = greatest(d.max_euro, t.max_euro)
+                from temp_daily t
+                where d.coin_id = $1 and d.date = t.date and t.date = any($2)", &[&coin_id, &dates])?;
+        }
+
+        let inserted = transaction.execute(
+            "Insert into daily_stats(coin_id, date, min_euro, average_euro, median_euro, std_dev, max_euro)
+            select $1, t.* from temp_daily t
+            where not exists (select 1 from daily_stats d where d.coin_id = $1 and d.date = t.date)
+            order by t.date", &[&coin_id])?;
+
+        Ok(Import { inserted, unchanged: unchanged.len(), conflicts: conflicting })
+    })
+}
+
+
+fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let overwrite = args.iter().any(|a| a == "--overwrite");
+    let args = args.iter().filter(|a| *a != "--overwrite").collect::<Vec<_>>();
+    if args.len() < 2 {
+        return Err(USAGE.into());
+    }
+
+    let mut points = Vec::new();
+    for path in &args[1..] {
+        points.extend(read(path)?);
+    }
+
+    let mut db = db::DB::new()?;
+    let (coin_id, name) = find_coin(&mut db, args[0])?;
+    let result = import(&mut db, coin_id, &points, overwrite)?;
+
+    println!("{}: {} prices read, {} days imported, {} unchanged, {} conflicts{}", name, points.len(), result.inserted,
+             result.unchanged, result.conflicts.len(),
+             match (result.conflicts.is_empty(), overwrite) {
+                 (true, _) => "",
+                 (false, true) => " updated",
+                 (false, false) => " kept, use --overwrite to update them"
+             });
+    for c in result.conflicts.iter().take(MAX_CONFLICTS_SHOWN) {
+        println!("  {}: existing average €{} imported €{}", c.date, c.existing.round_dp(2), c.imported.round_dp(2));
+    }
+    if result.conflicts.len() > MAX_CONFLICTS_SHOWN {
+        println!("  and {} more", result.conflicts.len() - MAX_CONFLICTS_SHOWN);
+    }
+
+    Ok(())
+}
+
+
+fn main() {
+    env_logger::init();
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(d: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap()
+    }
+
+    macro_rules! parse_date_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, expected): (&str, Option<&str>) = $value;
+                assert_eq!(expected.map(date), parse_date(input));
+            }
+        )*
+        }
+    }
+
+    parse_date_tests! {
+        parse_date_0: ("2021-06-01", Some("2021-06-01")),
+        parse_date_1: ("2021-06-01 00:00:00 UTC", Some("2021-06-01")),
+        parse_date_2: ("2021-06-01T12:30:00Z", Some("2021-06-01")),
+        parse_date_3: ("1622505600", Some("2021-06-01")),
+        parse_date_4: ("1622505600000", Some("2021-06-01")),
+        parse_date_5: ("June 1st", None),
+    }
+
+    macro_rules! conflicts_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (existing, imported, expected): (&str, &str, bool) = $value;
+                assert_eq!(expected, conflicts(existing.parse().unwrap(), imported.parse().unwrap()));
+            }
+        )*
+        }
+    }
+
+    conflicts_tests! {
+        conflicts_0: ("30000", "30000", false),
+        conflicts_1: ("30000", "31500", false),
+        conflicts_2: ("30000", "28500", false),
+        conflicts_3: ("30000", "31500.01", true),
+        conflicts_4: ("30000", "20000", true),
+        conflicts_5: ("0.0001", "0.0002", true),
+    }
+
+    #[test]
+    fn coingecko_csv() {
+        let csv = "snapped_at,price,market_cap,total_volume\n\
+                   2021-06-01 00:00:00 UTC,30123.4567,561234567890.1,0.0\n\
+                   2021-06-02 00:00:00 UTC,30500.1,571234567890.1,1.0\n";
+        assert_eq!(vec![(date("2021-06-01"), "30123.4567".parse().unwrap()), (date("2021-06-02"), "30500.1".parse().unwrap())],
+                   parse_csv(csv).unwrap());
+        assert!(parse_csv("snapped_at,price\n2021-06-01,n/a\n").is_err());
+        assert!(parse_csv("day,value\n2021-06-01,1\n").is_err());
+        assert!(parse_csv("snapped_at,price\n2021-06-01,0\n").is_err());
+        assert!(parse_csv("snapped_at,price\n2021-06-01,-1\n").is_err());
+    }
+
+    #[test]
+    fn coingecko_json() {
+        let chart = r#"{"prices": [[1622505600000, 30123.4567], [1622592000000, 30500.1]], "market_caps": []}"#;
+        let list = r#"[{"date": "2021-06-01", "price": 30123.4567}, {"snapped_at": 1622592000, "close": 30500.1}]"#;
+        let expected = vec![(date("2021-06-01"), "30123.4567".parse().unwrap()), (date("2021-06-02"), "30500.1".parse().unwrap())];
+
+        assert_eq!(expected, parse_json(chart).unwrap());
+        assert_eq!(expected, parse_json(list).unwrap());
+        assert!(parse_json(r#"{"prices": [[1622505600000, null]]}"#).is_err());
+        assert!(parse_json(r#"{"prices": [[1622505600000, 0]]}"#).is_err());
+    }
+}
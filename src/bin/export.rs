@@ -0,0 +1,75 @@
+use std::{env, error::Error, fs::File, io, path::Path, process};
+
+use chrono::{NaiveDate, Utc};
+use rooney::db;
+use rooney::export::{self, Format, Table};
+
+
+const USAGE: &str = "Usage: export <prices|daily_stats> <coin|ticker,...> <from> [to] [--format csv|parquet] [--output file]
+Dates are inclusive and to defaults to today. The format defaults to the output file's extension, or csv.";
+
+
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| format!("Invalid date, expected YYYY-MM-DD: {}", date))
+}
+
+
+// Removes `--flag value` from the arguments and returns the value.
+fn take_option(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|a| a == flag) {
+        Some(i) if i + 1 < args.len() => Ok(Some(args.drain(i..=i + 1).nth(1).unwrap_or_default())),
+        Some(_) => Err(format!("{} needs a value", flag)),
+        None => Ok(None)
+    }
+}
+
+
+fn find_coins(db: &db::DB, coins: &str) -> Result<Vec<String>, String> {
+    coins.split(',').filter(|c| !c.is_empty()).map(|c| {
+        let c = c.to_lowercase();
+        match db.all_coins.contains(&c) {
+            true => Ok(c),
+            false => db.nicks_coins.get(&c).cloned().ok_or_else(|| format!("Unknown coin: {}", c))
+        }
+    }).collect()
+}
+
+
+fn run(mut args: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let output = take_option(&mut args, "--output")?;
+    let format = match take_option(&mut args, "--format")? {
+        Some(f) => f,
+        None => output.as_ref().and_then(|o| Path::new(o).extension()).and_then(|e| e.to_str()).unwrap_or("csv").to_string()
+    };
+    let format = Format::parse(&format).ok_or_else(|| format!("Unknown format: {}", format))?;
+
+    let (table, coins, from, to) = match args.as_slice() {
+        [table, coins, from] => (table, coins, parse_date(from)?, Utc::now().date_naive()),
+        [table, coins, from, to] => (table, coins, parse_date(from)?, parse_date(to)?),
+        _ => return Err(USAGE.into())
+    };
+    let table = Table::parse(table).ok_or_else(|| format!("Unknown table: {}", table))?;
+    if from > to {
+        return Err(format!("{} is after {}", from, to).into());
+    }
+
+    let mut db = db::DB::new()?;
+    let coins = find_coins(&db, coins)?;
+    let count = match &output {
+        Some(path) => export::write(&mut db, table, format, &coins, from, to, io::BufWriter::new(File::create(path)?))?,
+        None => export::write(&mut db, table, format, &coins, from, to, io::BufWriter::new(io::stdout()))?
+    };
+
+    eprintln!("Exported {} {} rows for {} from {} to {}", count, table.name(), coins.join(", "), from, to);
+    Ok(())
+}
+
+
+fn main() {
+    env_logger::init();
+
+    if let Err(e) = run(env::args().skip(1).collect()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -21,11 +21,11 @@ impl Advice {
     fn query(&self, db: &mut db::DB) -> Option<String> {
         let query = "select response from advice offset floor(random()*(select count(*) from advice)) limit 1;";
 
-        let rows = db.connection.query(query, &[]).unwrap();
+        let rows = db.query("advice", query, &[]).unwrap();
         if rows.is_empty() {
             return None
         }
-        Some(rows.get(0).unwrap().get(0))
+        Some(rows[0].get(0))
     }
 }
 
@@ -1,81 +1,39 @@
 use std::fmt;
 use titlecase::titlecase;
 
-use super::{db, Command, CommandArgs, Error, formatter::format_currency, Result};
+use rooney::{ats, prices};
+
+use super::{db, Command, CommandArgs, Error, formatter::{format_age, format_currency}, Result};
 
 use chrono::NaiveDate;
+use rust_decimal::Decimal;
 
 
+#[allow(clippy::upper_case_acronyms)]
 pub(super) struct ATS;
 
 
 struct _ATS {
     name: String,
-    lowest: f32,
+    lowest: Decimal,
     lowest_date: NaiveDate,
-    highest: f32,
+    highest: Decimal,
     highest_date: NaiveDate,
+    // Seconds since the coin's last price was written.
+    age: i64,
 }
 
 
 impl ATS {
     fn query(&self, db: &mut db::DB, coin: String) -> Option<_ATS> {
-        let query =
-            "with all_ats as (
-                select min(euro) as lowest, max(euro) as ath
-                from prices
-                join coins using(coin_id)
-                where name = $1
-                union select min_euro as lowest, max_euro as ath
-                from daily_stats
-                join coins using(coin_id)
-                where name = $1
-            ),
-            extremes as (
-                select min(lowest) as minimum, max(ath) as ath
-                from all_ats
-            ),
-            lowest as (
-                select time::date as date, euro as price
-                from prices
-                join coins using(coin_id)
-                where euro=(select minimum from extremes)
-                and name = $1
-                union select date, min_euro as price
-                from daily_stats
-                join coins using(coin_id)
-                where min_euro=(select minimum from extremes)
-                and name = $1
-                limit 1
-            ),
-            highest as (
-                select time::date as date, euro as price
-                from prices
-                join coins using(coin_id)
-                where euro=(select ath from extremes)
-                and name = $1
-                union select date, max_euro as price
-                from daily_stats
-                join coins using(coin_id)
-                where max_euro=(select ath from extremes)
-                and name = $1
-                limit 1
-            )
-            select date, cast(price as real) from lowest union select date, cast(price as real) from highest
-            order by price asc";
-
-        let rows = db.connection.query(query, &[&coin]).unwrap();
-        if rows.len() < 2 {
-            return None;
-        }
-
-        let (lowest, highest) = (rows.get(0).unwrap(), rows.get(1).unwrap());
-        Some(_ATS {
-            name: coin,
-            lowest_date: lowest.get(0),
-            lowest: lowest.get(1),
-            highest_date: highest.get(0),
-            highest: highest.get(1)
+        let age = prices::last_known(db, std::slice::from_ref(&coin)).unwrap().first().map_or(0, |p| p.1);
+        ats::query(db, &coin).unwrap().map(|a| _ATS {
+            name: a.name,
+            lowest: a.lowest,
+            lowest_date: a.lowest_date,
+            highest: a.highest,
+            highest_date: a.highest_date,
+            age,
         })
     }
 }
@@ -88,7 +46,7 @@ impl Command for ATS {
 
     fn run(&self, db: &mut db::DB, msg: &Option<&str>) -> Result<String> {
         let commands: Vec<&str> = msg.unwrap().split_whitespace().collect();
-        let coin = self.get_coin(&db, self.parse_coin_arg(&commands));
+        let coin = self.get_coin(db, self.parse_coin_arg(&commands));
         let ats = self.query(db, coin);
 
         match ats {
@@ -105,8 +63,9 @@ impl Command for ATS {
 
 impl fmt::Display for _ATS {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "All time \x0305Low\x03/\x0303High\x03 Prices for {}, Lowest: \x0305€{}\x03 on {} Highest: \x0303€{}\x03 on {}",
-            titlecase(&self.name), format_currency(self.lowest), self.lowest_date, format_currency(self.highest), self.highest_date
+        write!(f, "All time \x0305Low\x03/\x0303High\x03 Prices for {}, Lowest: \x0305€{}\x03 on {} Highest: \x0303€{}\x03 on {}{}",
+            titlecase(&self.name), format_currency(self.lowest), self.lowest_date, format_currency(self.highest), self.highest_date,
+            format_age(self.age)
         )
     }
 }
@@ -0,0 +1,167 @@
+use std::fmt;
+use titlecase::titlecase;
+
+use rooney::{prices, window::Window};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+use super::{db, Command, CommandArgs, Error, Result};
+use super::formatter::{format_change, format_currency};
+
+pub(super) struct Chart {
+    pub url: Option<String>
+}
+
+
+const WIDTH: usize = 60;
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+
+struct _Chart {
+    name: String,
+    ticker: String,
+    window: Window,
+    prices: Vec<Decimal>,
+}
+
+
+impl Chart {
+    fn query(&self, db: &mut db::DB, coin: &str, window: Window) -> Option<_Chart> {
+        let series = prices::query(db, coin, window.unit, &window.interval).unwrap();
+        let first = series.first()?;
+
+        Some(_Chart {
+            name: first.name.clone(),
+            ticker: first.ticker.clone(),
+            prices: series.iter().map(|p| p.euro).collect(),
+            window,
+        })
+    }
+}
+
+
+impl Command for Chart {
+    fn name(&self) -> &'static str {
+        "!chart"
+    }
+
+    fn run(&self, db: &mut db::DB, msg: &Option<&str>) -> Result<String> {
+        let commands: Vec<&str> = msg.unwrap().split_whitespace().collect();
+        let coin = self.get_coin(db, self.parse_coin_arg(&commands));
+        let window = self.parse_window(&commands).unwrap_or_else(Window::last_24_hours);
+
+        let link = self.url.as_ref().map(|u| format!(" {}/{}.png?range={}", u.trim_end_matches('/'), encode(&coin), window.arg));
+
+        match self.query(db, &coin, window) {
+            Some(c) => Ok(format!("{}{}", c, link.unwrap_or_default())),
+            None => Err(Error::Contact)
+        }
+    }
+
+    fn help(&self) -> &'static str {
+        "!chart [coin|ticker] [window]: Sparkline of a coin's price. Defaults to btc over the last 24 hours, \
+            windows can be 1h, 24h, 7d, 30d or ytd."
+    }
+}
+
+
+impl CommandArgs for Chart {}
+
+
+impl fmt::Display for _Chart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let first = self.prices[0];
+        let last = self.prices[self.prices.len() - 1];
+        let min = self.prices.iter().min().copied().unwrap_or_default();
+        let max = self.prices.iter().max().copied().unwrap_or_default();
+        let colour = if last < first { "05" } else { "03" };
+        let change = ((last - first) * Decimal::ONE_HUNDRED).checked_div(first).and_then(|c| c.to_f32()).unwrap_or_default();
+        let points = self.prices.iter().filter_map(|p| p.to_f32()).collect::<Vec<_>>();
+
+        write!(f, "{} ({}) {}: Low €{} \x03{}{}\x03 High €{} {}\x03",
+                titlecase(&self.name), self.ticker.to_uppercase(), self.window.long, format_currency(min), colour,
+                sparkline(&downsample(&points, WIDTH)), format_currency(max), format_change(change))
+    }
+}
+
+
+// Averages the series into at most `width` evenly sized buckets.
+fn downsample(values: &[f32], width: usize) -> Vec<f32> {
+    if values.len() <= width {
+        return values.to_vec();
+    }
+
+    (0..width).map(|i| {
+        let bucket = &values[i * values.len() / width..(i + 1) * values.len() / width];
+        bucket.iter().sum::<f32>() / bucket.len() as f32
+    }).collect()
+}
+
+
+fn encode(coin: &str) -> String {
+    coin.bytes().map(|b| match b {
+        b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'~' => (b as char).to_string(),
+        _ => format!("%{:02X}", b)
+    }).collect()
+}
+
+
+fn sparkline(values: &[f32]) -> String {
+    let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let range = max - min;
+
+    values.iter().map(|v| {
+        match range > 0.0 {
+            true => BARS[(((v - min) / range) * (BARS.len() - 1) as f32).round() as usize],
+            false => BARS[BARS.len() / 2]
+        }
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! sparkline_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, expected): (Vec<f32>, &str) = $value;
+                assert_eq!(expected, sparkline(&input));
+            }
+        )*
+        }
+    }
+
+    macro_rules! downsample_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, width, expected): (Vec<f32>, usize, Vec<f32>) = $value;
+                assert_eq!(expected, downsample(&input, width));
+            }
+        )*
+        }
+    }
+
+    sparkline_tests! {
+        sparkline_0: (vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0], "▁▂▃▄▅▆▇█"),
+        sparkline_1: (vec![8.0, 1.0], "█▁"),
+        sparkline_2: (vec![5.0, 5.0, 5.0], "▅▅▅"),
+        sparkline_3: (vec![], ""),
+    }
+
+    #[test]
+    fn encode_coin() {
+        assert_eq!("bitcoin%20cash", encode("bitcoin cash"));
+    }
+
+    downsample_tests! {
+        downsample_0: (vec![1.0, 2.0, 3.0], 5, vec![1.0, 2.0, 3.0]),
+        downsample_1: (vec![1.0, 3.0, 5.0, 7.0], 2, vec![2.0, 6.0]),
+        downsample_2: (vec![1.0, 2.0, 3.0, 4.0, 5.0], 2, vec![1.5, 4.0]),
+    }
+}
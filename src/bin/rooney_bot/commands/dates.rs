@@ -0,0 +1,201 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+
+
+#[derive(Debug, PartialEq)]
+pub(super) enum Period {
+    Day(NaiveDate),
+    Since(NaiveDate),
+    Range(NaiveDate, NaiveDate),
+}
+
+
+impl Period {
+    // Ranges must run forwards and are cut short at today, so the current month or year gives the days so far.
+    // Nothing may start after today, and periods since today have no days to show yet.
+    pub(super) fn clamp(self, today: NaiveDate) -> Result<Period, String> {
+        match self {
+            Period::Range(start, end) if start >= end =>
+                Err(format!("Invalid date range: {} is not before {}", start, end)),
+            Period::Day(d) | Period::Since(d) | Period::Range(d, _) if d > today =>
+                Err(format!("Invalid date: {} is in the future", d)),
+            Period::Since(d) if d == today => Err(format!("No days since {} yet", d)),
+            Period::Range(start, end) => Ok(Period::Range(start, end.min(today))),
+            period => Ok(period)
+        }
+    }
+}
+
+
+// Whether a word is meant as a date rather than a coin, so typos in coins aren't reported as bad dates.
+pub(super) fn looks_like_date(word: &str) -> bool {
+    let word = word.to_lowercase();
+    word.chars().any(|c| c.is_ascii_digit() || c == '-')
+        || ["today", "yesterday", "ytd", "last"].contains(&word.as_str())
+        || (word.ends_with("day") && Weekday::from_str(&word).is_ok())
+}
+
+
+// Parses a date expression from the end of the arguments, returning it and how many words it used.
+pub(super) fn parse(words: &[&str], today: NaiveDate) -> Option<(Period, usize)> {
+    if words.len() >= 2 {
+        if let Some(p) = parse_pair(words[words.len() - 2], words[words.len() - 1], today) {
+            return Some((p, 2));
+        }
+    }
+
+    words.last().and_then(|w| parse_word(w, today)).map(|p| (p, 1))
+}
+
+
+fn parse_pair(first: &str, second: &str, today: NaiveDate) -> Option<Period> {
+    if first.to_lowercase() == "last" {
+        return match second.to_lowercase().as_str() {
+            "week" => Some(Period::Since(today - Duration::weeks(1))),
+            "month" => today.checked_sub_months(Months::new(1)).map(Period::Since),
+            "year" => today.checked_sub_months(Months::new(12)).map(Period::Since),
+            _ => None
+        };
+    }
+
+    match (NaiveDate::from_str(first), NaiveDate::from_str(second)) {
+        (Ok(start), Ok(end)) => Some(Period::Range(start, end)),
+        _ => None
+    }
+}
+
+
+fn parse_word(word: &str, today: NaiveDate) -> Option<Period> {
+    let word = word.to_lowercase();
+
+    match word.as_str() {
+        "today" => return Some(Period::Day(today)),
+        "yesterday" => return Some(Period::Day(today - Duration::days(1))),
+        "ytd" => return NaiveDate::from_ymd_opt(today.year(), 1, 1).map(Period::Since),
+        _ => ()
+    }
+
+    if let Ok(date) = NaiveDate::from_str(&word) {
+        return Some(Period::Day(date));
+    }
+
+    if let Some(weekday) = Weekday::from_str(&word).ok().filter(|_| word.ends_with("day")) {
+        let days_back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday() - 1) % 7 + 1;
+        return Some(Period::Day(today - Duration::days(i64::from(days_back))));
+    }
+
+    parse_month(&word).or_else(|| parse_relative(&word, today))
+}
+
+
+fn parse_month(word: &str) -> Option<Period> {
+    let start = NaiveDate::from_str(&format!("{}-01", word)).ok()?;
+    let end = start.checked_add_months(Months::new(1))? - Duration::days(1);
+    Some(Period::Range(start, end))
+}
+
+
+fn parse_relative(word: &str, today: NaiveDate) -> Option<Period> {
+    let unit = word.chars().last()?;
+    let amount = u32::from_str(&word[..word.len() - unit.len_utf8()]).ok()?;
+
+    match unit {
+        'd' => today.checked_sub_signed(Duration::days(i64::from(amount))),
+        'w' => today.checked_sub_signed(Duration::weeks(i64::from(amount))),
+        'm' => today.checked_sub_months(Months::new(amount)),
+        'y' => today.checked_sub_months(Months::new(amount.checked_mul(12)?)),
+        _ => None
+    }.map(Period::Since)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::from_str(s).unwrap()
+    }
+
+    macro_rules! parse_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, expected): (&str, Option<(Period, usize)>) = $value;
+                let words = input.split_whitespace().collect::<Vec<_>>();
+                // 2021-06-16 is a Wednesday.
+                assert_eq!(expected, parse(&words, date("2021-06-16")));
+            }
+        )*
+        }
+    }
+
+    parse_tests! {
+        parse_0: ("btc 2021-01-01", Some((Period::Day(date("2021-01-01")), 1))),
+        parse_1: ("btc 7d", Some((Period::Since(date("2021-06-09")), 1))),
+        parse_2: ("eth last week", Some((Period::Since(date("2021-06-09")), 2))),
+        parse_3: ("btc monday", Some((Period::Day(date("2021-06-14")), 1))),
+        parse_4: ("btc wednesday", Some((Period::Day(date("2021-06-09")), 1))),
+        parse_5: ("btc 2021-02", Some((Period::Range(date("2021-02-01"), date("2021-02-28")), 1))),
+        parse_6: ("btc 2021-01-01 2021-06-01", Some((Period::Range(date("2021-01-01"), date("2021-06-01")), 2))),
+        parse_7: ("btc yesterday", Some((Period::Day(date("2021-06-15")), 1))),
+        parse_8: ("btc 3m", Some((Period::Since(date("2021-03-16")), 1))),
+        parse_9: ("btc ytd", Some((Period::Since(date("2021-01-01")), 1))),
+        parse_10: ("btc", None),
+        parse_11: ("btc 2021-13-01", None),
+        parse_12: ("", None),
+        parse_13: ("sun", None),
+    }
+
+    macro_rules! clamp_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (period, expected): (Period, Option<Period>) = $value;
+                assert_eq!(expected, period.clamp(date("2021-06-16")).ok());
+            }
+        )*
+        }
+    }
+
+    clamp_tests! {
+        clamp_0: (Period::Day(date("2021-06-16")), Some(Period::Day(date("2021-06-16")))),
+        clamp_1: (Period::Day(date("2021-06-17")), None),
+        clamp_2: (Period::Since(date("2021-06-17")), None),
+        clamp_3: (Period::Range(date("2021-01-01"), date("2021-06-16")),
+                  Some(Period::Range(date("2021-01-01"), date("2021-06-16")))),
+        clamp_4: (Period::Range(date("2021-01-01"), date("2999-01-01")),
+                  Some(Period::Range(date("2021-01-01"), date("2021-06-16")))),
+        clamp_5: (Period::Range(date("2021-06-01"), date("2021-01-01")), None),
+        clamp_6: (Period::Range(date("2021-06-01"), date("2021-06-01")), None),
+        clamp_7: (Period::Range(date("2021-06-17"), date("2021-06-18")), None),
+        clamp_8: (Period::Range(date("2021-06-01"), date("2021-06-30")),
+                  Some(Period::Range(date("2021-06-01"), date("2021-06-16")))),
+        clamp_9: (Period::Since(date("2021-06-16")), None),
+        clamp_10: (Period::Since(date("2021-06-15")), Some(Period::Since(date("2021-06-15")))),
+    }
+
+    macro_rules! looks_like_date_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, expected) = $value;
+                assert_eq!(expected, looks_like_date(input));
+            }
+        )*
+        }
+    }
+
+    looks_like_date_tests! {
+        looks_like_date_0: ("2021-13-01", true),
+        looks_like_date_1: ("7x", true),
+        looks_like_date_2: ("Sunday", true),
+        looks_like_date_3: ("ytd", true),
+        looks_like_date_4: ("foo", false),
+        looks_like_date_5: ("sun", false),
+    }
+}
@@ -1,10 +1,13 @@
 use chrono::{NaiveDate, NaiveDateTime};
+use rooney::candles;
+use rust_decimal::Decimal;
 use std::fmt;
 use titlecase::titlecase;
 
-use super::{db, Command, CommandArgs, Error, Result};
+use super::{db, Command, CommandArgs, Error, Result, sort_by_coins, with_ignored};
+use super::dates::Period;
 
-use super::formatter::{format_change, format_currency};
+use super::formatter::{format_age, format_change, format_currency};
 
 pub(super) struct Diff;
 
@@ -14,38 +17,66 @@ struct _Diff {
     ticker: String,
     start: NaiveDate,
     end: NaiveDateTime,
-    first: f32,
-    last: f32,
+    first: Decimal,
+    last: Decimal,
     diff: f32,
+    // Seconds since the last price was written, always 0 for ranges of daily stats.
+    age: i64,
+    to_date: bool,
 }
 
 
 impl Diff {
-    fn query(&self, db: &mut db::DB, coin: String, date: NaiveDate) -> Option<_Diff> {
-        let query =
+    fn query(&self, db: &mut db::DB, coins: &[String], date: NaiveDate) -> Vec<_Diff> {
+        let query = |prices: &str| format!(
             "with first as (
                 select coin_id, date, average_euro as first
                 from daily_stats
                 join coins using(coin_id)
-                where name = ($1)
-                and date = ($2)
+                where name = any($1)
+                and date = $2
+            ),
+            latest as (
+                select distinct on (coin_id) coin_id, time, euro
+                from {}
+                where coin_id in (select coin_id from first)
+                order by coin_id, time desc
             )
-            select name, ticker, date, date_trunc('minute', time) as latest, cast(first as real), cast(euro as real) as last,
-            cast((euro-first)*100/first as real) as diff
+            select name, ticker, date, date_trunc('minute', time) as latest, first, euro as last,
+            cast((euro-first)*100/first as real) as diff, extract(epoch from now()::timestamp - time)::bigint as age
             from first
-            join prices using(coin_id)
+            join latest using(coin_id)
+            join coins using(coin_id);", prices);
+
+        let rows = candles::query_history(db, "diff", query, &[&coins, &date]).unwrap();
+
+        sort_by_coins(coins, rows.iter().map(|row| _Diff {
+            name: row.get(0),
+            ticker: row.get(1),
+            start: row.get(2),
+            end: row.get(3),
+            first: row.get(4),
+            last: row.get(5),
+            diff: row.get(6),
+            age: row.get(7),
+            to_date: true,
+        }).collect(), |d| &d.name)
+    }
+
+    fn query_range(&self, db: &mut db::DB, coins: &[String], start: NaiveDate, end: NaiveDate) -> Vec<_Diff> {
+        let query =
+            "select name, ticker, s.date, cast(e.date as timestamp), s.average_euro,
+            e.average_euro, cast((e.average_euro-s.average_euro)*100/s.average_euro as real) as diff
+            from daily_stats s
+            join daily_stats e using(coin_id)
             join coins using(coin_id)
-            where name = ($1)
-            order by time desc limit 1;";
-    
-        let rows = db.connection.query(query, &[&coin, &date]).unwrap();
-    
-        if rows.is_empty() {
-            return None;
-        }
-    
-        let row = rows.get(0).unwrap();
-        Some(_Diff{
+            where name = any($1)
+            and s.date = $2
+            and e.date = $3;";
+
+        let rows = db.query("diff_range", query, &[&coins, &start, &end]).unwrap();
+
+        sort_by_coins(coins, rows.iter().map(|row| _Diff {
             name: row.get(0),
             ticker: row.get(1),
             start: row.get(2),
@@ -53,17 +84,40 @@ impl Diff {
             first: row.get(4),
             last: row.get(5),
             diff: row.get(6),
-        })
+            age: 0,
+            to_date: false,
+        }).collect(), |d| &d.name)
     }
-    
 }
 
 
 impl fmt::Display for _Diff {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Diff for {} ({}) from {} to {}: First: €{} Latest: €{} Diff: {} To Date",
+        if !self.to_date {
+            return write!(f, "Diff for {} ({}) from {} to {}: First: €{} Last: €{} Diff: {}",
+                          titlecase(&self.name), self.ticker.to_uppercase(), self.start, self.end.date(),
+                          format_currency(self.first), format_currency(self.last), format_change(self.diff));
+        }
+
+        write!(f, "Diff for {} ({}) from {} to {}: First: €{} Latest: €{}{} Diff: {} To Date",
                 titlecase(&self.name), self.ticker.to_uppercase(), self.start, self.end,
-                format_currency(self.first), format_currency(self.last), format_change(self.diff))
+                format_currency(self.first), format_currency(self.last), format_age(self.age), format_change(self.diff))
+    }
+}
+
+
+impl _Diff {
+    fn compact(diffs: &[_Diff]) -> String {
+        let start = diffs[0].start;
+        let end = match diffs[0].to_date {
+            true => "date".to_string(),
+            false => diffs[0].end.date().to_string()
+        };
+        let diffs = diffs.iter().map(|d| format!("{} ({}): €{} to €{}{} {}\x03", titlecase(&d.name), d.ticker.to_uppercase(),
+                                                 format_currency(d.first), format_currency(d.last), format_age(d.age),
+                                                 format_change(d.diff)))
+                         .collect::<Vec<_>>();
+        format!("Diff from {} to {}: {}", start, end, diffs.join(" | "))
     }
 }
 
@@ -75,18 +129,22 @@ impl Command for Diff {
 
     fn run(&self, db: &mut db::DB, msg: &Option<&str>) -> Result<String> {
         let commands: Vec<&str> = msg.unwrap().split_whitespace().collect();
-        let coin = self.get_coin(&db, self.parse_coin_arg(&commands));
-        let date = self.parse_date(&commands);
-        let diff = self.query(db, coin, date);
+        let (coins, ignored, period) = self.parse_period(db, &commands)?;
+        let diffs = match period {
+            Period::Day(date) | Period::Since(date) => self.query(db, &coins, date),
+            Period::Range(start, end) => self.query_range(db, &coins, start, end),
+        };
 
-        match diff {
-            Some(d) => Ok(d.to_string()),
-            None => Err(Error::Contact)
-        }
+        with_ignored(match diffs.as_slice() {
+            [] => Err(Error::Contact),
+            [d] if coins.len() == 1 => Ok(d.to_string()),
+            ds => Ok(_Diff::compact(ds))
+        }, &ignored)
     }
 
     fn help(&self) -> &'static str {
-        "!diff [coin|ticker] [date]: Get the difference in price between the start date and current price. \
+        "!diff [coin|ticker ...] [date|range]: Get the difference in price between the start date and current price \
+            for up to 5 coins. Dates can be 2021-01-01, monday, 7d or last week, or give two dates for a range. \
             Defaults to btc and yesterday's date"
     }
 }
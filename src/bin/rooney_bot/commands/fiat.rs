@@ -2,7 +2,11 @@ use std::fmt;
 
 use titlecase::titlecase;
 
-use super::{db, Command, CommandArgs, Error, formatter::format_currency, price, Result};
+use rooney::prices;
+use rust_decimal::Decimal;
+
+use super::{db, Command, CommandArgs, Error, Result};
+use super::formatter::{format_age, format_currency};
 
 pub(super) struct Fiat;
 
@@ -10,20 +14,26 @@ pub(super) struct Fiat;
 struct _Fiat {
     name: String,
     ticker: String,
-    amount: f32,
-    euro: f32,
+    amount: Decimal,
+    euro: Decimal,
+    worth: Decimal,
+    age: i64,
 }
 
 
 impl Fiat {
-    fn query(&self, db: &mut db::DB, coin: String, amount: f32) -> Option<_Fiat> {
-        let price = price::Coin.query(db, &coin).unwrap();
-    
-        Some(_Fiat {
+    fn query(&self, db: &mut db::DB, coin: String, amount: Decimal) -> Result<_Fiat> {
+        let (price, age) = prices::last_known(db, std::slice::from_ref(&coin)).map_err(|_| Error::Contact)?
+            .pop().ok_or(Error::Contact)?;
+        let worth = amount.checked_mul(price.euro).ok_or_else(|| Error::Usage(format!("{} is too many coins", amount)))?;
+
+        Ok(_Fiat {
             name: coin,
             amount,
             ticker: price.ticker,
-            euro: price.euro
+            euro: price.euro,
+            worth,
+            age
         })
     }
 }
@@ -36,14 +46,9 @@ impl Command for Fiat {
 
     fn run(&self, db: &mut db::DB, msg: &Option<&str>) -> Result<String> {
         let commands: Vec<&str> = msg.unwrap().split_whitespace().collect();
-        let coin = self.get_coin(&db, self.parse_coin_arg(&commands));
+        let coin = self.get_coin(db, self.parse_coin_arg(&commands));
         let amount = self.parse_amount(&commands);
-        let fiat = self.query(db, coin, amount);
-
-        match fiat {
-            Some(f) => Ok(f.to_string()),
-            None => Err(Error::Contact)
-        }
+        Ok(self.query(db, coin, amount)?.to_string())
     }
 
     fn help(&self) -> &'static str {
@@ -58,7 +63,8 @@ impl CommandArgs for Fiat {}
 
 impl fmt::Display for _Fiat {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {} ({}) is worth €{} at €{} per coin", self.amount, titlecase(&self.name),
-                self.ticker.to_uppercase(), format_currency(self.amount * self.euro), format_currency(self.euro))
+        write!(f, "{} {} ({}) is worth €{} at €{} per coin{}", self.amount.normalize(), titlecase(&self.name),
+                self.ticker.to_uppercase(), format_currency(self.worth), format_currency(self.euro),
+                format_age(self.age))
     }
 }
@@ -1,23 +1,33 @@
-use separator::Separatable;
+use rooney::prices::STALE_AFTER_SECONDS;
+use rust_decimal::{Decimal, RoundingStrategy};
 
-pub fn format_currency(value: f32) -> String {
-    if value < 1.0 {
-        return format!("{:.8}", value);
+pub fn format_currency(value: Decimal) -> String {
+    if value < Decimal::ONE {
+        return format!("{:.8}", value.round_dp_with_strategy(8, RoundingStrategy::MidpointAwayFromZero));
     }
 
-    let v = (value * 100.0).round() / 100.0;
+    let s = format!("{:.2}", value.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero));
+    let (whole, cents) = s.split_at(s.len() - 3);
+    let digits = whole.chars().rev().collect::<Vec<_>>()
+        .chunks(3)
+        .map(|c| c.iter().rev().collect::<String>())
+        .rev()
+        .collect::<Vec<_>>();
 
-    let s = v.separated_string();
-    let parts: Vec<&str> = s.split('.').collect();
-    if parts.len() == 1 {
-        return format!("{}.00", s);
-    }
+    format!("{}{}", digits.join(","), cents)
+}
 
-    if parts[1].len() == 2 {
-        return s;
+// Large amounts such as market caps and supplies, shortened to thousands, millions, billions or trillions.
+pub fn format_amount(value: Decimal) -> String {
+    let round = |v: Decimal| v.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero);
+    for (exponent, suffix) in [(12, "T"), (9, "B"), (6, "M"), (3, "K")] {
+        let unit = Decimal::from(10i64.pow(exponent));
+        if value.abs() >= unit {
+            return format!("{:.2}{}", round(value / unit), suffix);
+        }
     }
 
-    format!("{}0", s)
+    format!("{:.2}", round(value))
 }
 
 pub fn format_change(diff: f32) -> String {
@@ -28,6 +38,27 @@ pub fn format_change(diff: f32) -> String {
     format!("\x0303Up: {:.2}%", diff)
 }
 
+pub fn is_stale(age: i64) -> bool {
+    age > *STALE_AFTER_SECONDS
+}
+
+pub fn format_age(age: i64) -> String {
+    describe_age(age, *STALE_AFTER_SECONDS)
+}
+
+fn describe_age(age: i64, stale_after: i64) -> String {
+    if age <= stale_after {
+        return "".to_string();
+    }
+
+    let (days, hours, minutes) = (age / 86400, age % 86400 / 3600, age % 3600 / 60);
+    match (days, hours) {
+        (0, 0) => format!(" ⚠ {}m old", minutes),
+        (0, _) => format!(" ⚠ {}h {}m old", hours, minutes),
+        _ => format!(" ⚠ {}d {}h old", days, hours)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -38,7 +69,19 @@ mod tests {
             #[test]
             fn $name() {
                 let (input, expected) = $value;
-                assert_eq!(expected, format_currency(input));
+                assert_eq!(expected, format_currency(input.parse().unwrap()));
+            }
+        )*
+        }
+    }
+
+    macro_rules! format_amount_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, expected) = $value;
+                assert_eq!(expected, format_amount(input.parse().unwrap()));
             }
         )*
         }
@@ -56,14 +99,47 @@ mod tests {
         }
     }
 
+    macro_rules! format_age_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, expected) = $value;
+                assert_eq!(expected, describe_age(input, 15 * 60));
+            }
+        )*
+        }
+    }
+
     format_currency_tests! {
-        format_currency_0: (1.0, "1.00"),
-        format_currency_1: (0.012345, "0.01234500"),
-        format_currency_2: (1.0, "1.00"),
+        format_currency_0: ("1.0", "1.00"),
+        format_currency_1: ("0.012345", "0.01234500"),
+        format_currency_2: ("1.0", "1.00"),
+        format_currency_3: ("43251.37", "43,251.37"),
+        format_currency_4: ("1234567.895", "1,234,567.90"),
+        format_currency_5: ("999.999", "1,000.00"),
+        format_currency_6: ("0.000000125", "0.00000013"),
+        format_currency_7: ("100", "100.00"),
+    }
+
+    format_amount_tests! {
+        format_amount_0: ("590123456789", "590.12B"),
+        format_amount_1: ("1234567890123.4", "1.23T"),
+        format_amount_2: ("19520000.0", "19.52M"),
+        format_amount_3: ("999.994", "999.99"),
+        format_amount_4: ("12345", "12.35K"),
     }
 
     format_change_tests! {
         format_change_0: (1.0, "\u{3}03Up: 1.00%"),
         format_change_1: (-50.0512, "\u{3}05Down: 50.05%"),
     }
-}
\ No newline at end of file
+
+    format_age_tests! {
+        format_age_0: (120, ""),
+        format_age_1: (900, ""),
+        format_age_2: (47 * 60 + 5, " ⚠ 47m old"),
+        format_age_3: (3 * 3600 + 5 * 60, " ⚠ 3h 5m old"),
+        format_age_4: (2 * 86400 + 4 * 3600 + 59, " ⚠ 2d 4h old"),
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+use postgres::error::SqlState;
+use rust_decimal::Decimal;
+use titlecase::titlecase;
+
+use rooney::{coins, prices, window::Window};
+
+use super::{db, Command, CommandArgs, Error, price, Result};
+use super::formatter::{format_age, format_amount, format_change, format_currency};
+
+
+pub(super) struct Info;
+
+
+struct _Info {
+    name: String,
+    ticker: String,
+    euro: Decimal,
+    dollar: Decimal,
+    // Today's change, None when there are no prices today and the last known price is shown.
+    change: Option<f32>,
+    age: i64,
+    metadata: Option<coins::Metadata>,
+}
+
+
+impl Info {
+    fn query(&self, db: &mut db::DB, coin: String) -> Result<_Info> {
+        let coins = std::slice::from_ref(&coin);
+        let info = match price::Coin.query(db, coins, &Window::today()).pop() {
+            Some(c) => _Info { name: c.name, ticker: c.ticker, euro: c.euro, dollar: c.dollar, change: Some(c.change),
+                               age: c.age, metadata: None },
+            None => prices::last_known(db, coins).map_err(|_| Error::Contact)?.pop().map(|(p, age)| _Info {
+                name: p.name, ticker: p.ticker, euro: p.euro, dollar: p.dollar, change: None, age, metadata: None
+            }).ok_or(Error::Contact)?
+        };
+        // coin_metadata only exists once the updater has fetched metadata.
+        let metadata = match coins::metadata(db, &info.name) {
+            Ok(m) => m,
+            Err(e) if e.code() == Some(&SqlState::UNDEFINED_TABLE) => None,
+            Err(_) => return Err(Error::Contact)
+        };
+
+        Ok(_Info { metadata, ..info })
+    }
+}
+
+
+impl Command for Info {
+    fn name(&self) -> &'static str {
+        "!info"
+    }
+
+    fn run(&self, db: &mut db::DB, msg: &Option<&str>) -> Result<String> {
+        let commands: Vec<&str> = msg.unwrap().split_whitespace().collect();
+        let coin = self.get_coin(db, self.parse_coin_arg(&commands));
+
+        Ok(self.query(db, coin)?.to_string())
+    }
+
+    fn help(&self) -> &'static str {
+        "!info [coin|ticker]: Get the rank, market cap, volume and supply of a coin along with its current price. \
+            Defaults to btc."
+    }
+}
+
+
+impl CommandArgs for Info {}
+
+
+impl fmt::Display for _Info {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rank = self.metadata.as_ref().and_then(|m| m.rank).map_or("".to_string(), |r| format!(" #{}", r));
+        write!(f, "{} ({}){}: €{} ${}{}", titlecase(&self.name), self.ticker.to_uppercase(), rank,
+               format_currency(self.euro), format_currency(self.dollar), format_age(self.age))?;
+        if let Some(change) = self.change {
+            write!(f, " {}\x03", format_change(change))?;
+        }
+
+        let m = match &self.metadata {
+            Some(m) => m,
+            None => return write!(f, " No market data yet")
+        };
+        let amount = |a: Option<_>| a.map_or("-".to_string(), format_amount);
+        let max_supply = m.max_supply.map_or("no max".to_string(), |s| format!("max {}", format_amount(s)));
+        // Priced from the latest price and circulating supply like !market, rather than the stored market cap.
+        let market_cap = m.circulating_supply.and_then(|s| self.euro.checked_mul(s));
+
+        write!(f, " Market Cap: €{} 24h Volume: €{} Supply: {} {} ({})", amount(market_cap), amount(m.volume_24h),
+               amount(m.circulating_supply), self.ticker.to_uppercase(), max_supply)
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+
+use postgres::error::SqlState;
+use rooney::market::{self, Overview};
+
+use super::{db, Command, CommandArgs, Error, Result};
+use super::formatter::{format_amount, format_change};
+
+
+pub(super) struct Market;
+
+
+struct _Market(Overview);
+
+
+impl Market {
+    fn query(&self, db: &mut db::DB) -> Result<_Market> {
+        // coin_metadata only exists once the updater has fetched metadata.
+        match market::overview(db) {
+            Ok(Some(o)) => Ok(_Market(o)),
+            Ok(None) => Err(Error::Usage("No market data yet".to_string())),
+            Err(e) if e.code() == Some(&SqlState::UNDEFINED_TABLE) => Err(Error::Usage("No market data yet".to_string())),
+            Err(_) => Err(Error::Contact)
+        }
+    }
+}
+
+
+impl Command for Market {
+    fn name(&self) -> &'static str {
+        "!market"
+    }
+
+    fn run(&self, db: &mut db::DB, _msg: &Option<&str>) -> Result<String> {
+        Ok(self.query(db)?.to_string())
+    }
+
+    fn help(&self) -> &'static str {
+        "!market: Get the total crypto market cap and its 24h change, 24h volume, BTC and ETH dominance and \
+            the number of coins tracked."
+    }
+}
+
+
+impl CommandArgs for Market {}
+
+
+impl fmt::Display for _Market {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let o = &self.0;
+        let percent = |p: Option<f32>| p.map_or("-".to_string(), |p| format!("{:.2}%", p));
+
+        write!(f, "Market Cap: €{}", format_amount(o.market_cap))?;
+        if let Some(c) = o.change {
+            write!(f, " {} 24h\x03", format_change(c))?;
+        }
+        if let Some(v) = o.volume_24h {
+            write!(f, " | 24h Volume: €{}", format_amount(v))?;
+        }
+        write!(f, " | BTC Dominance: {} | ETH Dominance: {} | {} coins tracked", percent(o.bitcoin_dominance),
+               percent(o.ethereum_dominance), o.coins)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! display_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, expected) = $value;
+                assert_eq!(expected, _Market(input).to_string());
+            }
+        )*
+        }
+    }
+
+    display_tests! {
+        display_0: (Overview {market_cap: "1234567890123.4".parse().unwrap(), change: Some(-1.5), volume_24h: Some("59012345678".parse().unwrap()),
+                              bitcoin_dominance: Some(51.234), ethereum_dominance: Some(17.5), coins: 100},
+                    "Market Cap: €1.23T \u{3}05Down: 1.50% 24h\u{3} | 24h Volume: €59.01B | BTC Dominance: 51.23% | ETH Dominance: 17.50% | 100 coins tracked"),
+        display_1: (Overview {market_cap: "19520000".parse().unwrap(), change: None, volume_24h: None,
+                              bitcoin_dominance: None, ethereum_dominance: None, coins: 1},
+                    "Market Cap: €19.52M | BTC Dominance: - | ETH Dominance: - | 1 coins tracked"),
+    }
+}
@@ -1,22 +1,42 @@
-use rooney::db;
+use rooney::{db, window};
 use std::cmp::Ordering;
 use std::str::FromStr;
 
-use chrono::{Duration, NaiveDate, Utc};
+use chrono::{Duration, Utc};
 use std::time::Instant;
 use failure::Fail;
+use lazy_static::lazy_static;
+use rust_decimal::Decimal;
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
 
 mod advice;
 mod ats;
+mod chart;
+mod dates;
 mod diff;
 mod fiat;
 mod formatter;
+mod info;
+mod market;
 mod movers;
 mod price;
 mod remark;
 mod stats;
 
 
+const MAX_COINS: usize = 5;
+
+
+lazy_static! {
+    static ref COMMANDS: IntCounterVec = register_int_counter_vec!(
+        "rooney_commands_total", "Commands handled by result.", &["command", "result"]
+    ).unwrap();
+    static ref COMMAND_SECONDS: HistogramVec = register_histogram_vec!(
+        "rooney_command_duration_seconds", "Time taken to reply to a command.", &["command"]
+    ).unwrap();
+}
+
+
 pub struct Commands {
     commands: Vec<Box<dyn Command>>,
     remark: Box<dyn Command>,
@@ -25,16 +45,17 @@ pub struct Commands {
 
 
 impl Commands {
-    pub(super) fn new() -> Result<Commands> {
+    pub(super) fn new(chart_url: Option<String>) -> Result<Commands> {
         let db = match db::DB::new() {
             Ok(db) => db,
             Err(_) => return Err(Error::Contact)
         };
 
         Ok(Self {
-            commands: vec![Box::new(advice::Advice::new()), Box::new(ats::ATS), Box::new(diff::Diff),
-                           Box::new(fiat::Fiat), Box::new(movers::Bulls), Box::new(movers::Bears),
-                           Box::new(price::Coin), Box::new(price::Coin24), Box::new(stats::Stats)],
+            commands: vec![Box::new(advice::Advice::new()), Box::new(ats::ATS), Box::new(chart::Chart { url: chart_url }),
+                           Box::new(diff::Diff), Box::new(fiat::Fiat), Box::new(info::Info), Box::new(market::Market),
+                           Box::new(movers::Bulls), Box::new(movers::Bears), Box::new(price::Coin),
+                           Box::new(price::Coin24), Box::new(stats::Stats)],
             remark: Box::new(remark::Remark::new()),
             db
         })
@@ -59,11 +80,23 @@ impl Commands {
             }
         }
         let c = self.commands.iter().find(|c| c.name() == command).unwrap_or(&self.remark);
-        c.run(&mut self.db, &Some(message))
+        let timer = COMMAND_SECONDS.with_label_values(&[c.name()]).start_timer();
+        let reply = c.run(&mut self.db, &Some(message));
+        timer.observe_duration();
+
+        let result = match &reply {
+            Ok(_) => "ok",
+            Err(Error::Contact) => "error",
+            Err(Error::Cooldown) => "cooldown",
+            Err(Error::Reply) => "no_reply",
+            Err(Error::Usage(_)) => "usage",
+        };
+        COMMANDS.with_label_values(&[c.name(), result]).inc();
+        reply
     }
 
     fn help(&self) -> Result<String> {
-        Ok("Commands: !advice !ats !bears !bulls !help !coin !diff !fiat !stats. \
+        Ok("Commands: !advice !ats !bears !bulls !chart !help !coin !diff !fiat !info !market !stats. \
             !help [command] for more information on a specific command.".to_string())
     }
 }
@@ -77,6 +110,8 @@ pub enum Error {
     Cooldown,
     #[fail(display = "No reply")]
     Reply,
+    #[fail(display = "{}", _0)]
+    Usage(String),
 }
 
 
@@ -97,6 +132,41 @@ trait Command {
 }
 
 
+// Picks up to MAX_COINS distinct coins from `words` in order. Words that are neither a coin nor a window, and coins
+// past the limit, are returned as ignored.
+fn pick_coins(words: &[&str], find: impl Fn(&str) -> Option<String>) -> (Vec<String>, Vec<String>) {
+    let mut coins: Vec<String> = Vec::new();
+    let mut ignored: Vec<String> = Vec::new();
+    for word in words.iter().filter(|w| window::Window::parse(w).is_none()) {
+        match find(&word.to_lowercase()) {
+            Some(coin) if coins.contains(&coin) => (),
+            Some(coin) if coins.len() < MAX_COINS => coins.push(coin),
+            _ => ignored.push(word.to_string())
+        }
+    }
+
+    if coins.is_empty() {
+        coins.push("bitcoin".to_string());
+    }
+    (coins, ignored)
+}
+
+
+// Mentions the ignored coin arguments after a reply so typos don't go unnoticed.
+fn with_ignored(reply: Result<String>, ignored: &[String]) -> Result<String> {
+    match ignored {
+        [] => reply,
+        _ => reply.map(|r| format!("{} (Ignored: {})", r, ignored.join(", ")))
+    }
+}
+
+
+fn sort_by_coins<T>(coins: &[String], mut results: Vec<T>, name: impl Fn(&T) -> &str) -> Vec<T> {
+    results.sort_by_key(|r| coins.iter().position(|c| c == name(r)));
+    results
+}
+
+
 trait CommandArgs {
     fn parse_coin_arg(&self, words: &[&str]) -> String {
         match words.len() {
@@ -104,35 +174,98 @@ trait CommandArgs {
             _ => words[1],
         }.to_string().to_lowercase()
     }
-    
-    fn get_coin(&self, db: &db::DB, coin: String) -> String {
-        if db.all_coins.contains(&coin) {
-            return coin;
+
+    // Coins given after the command along with the ignored arguments, defaulting to bitcoin.
+    fn parse_coin_args(&self, db: &db::DB, words: &[&str]) -> (Vec<String>, Vec<String>) {
+        pick_coins(&words[1..], |w| self.find_coin(db, w))
+    }
+
+    fn find_coin(&self, db: &db::DB, coin: &str) -> Option<String> {
+        if db.all_coins.contains(coin) {
+            return Some(coin.to_string());
         }
 
-        match db.nicks_coins.get(&coin) {
-            Some(c) => c,
-            None => "bitcoin"
-        }.to_string()
+        db.nicks_coins.get(coin).cloned()
     }
-    
-    fn parse_date(&self, words: &[&str]) -> NaiveDate {
-        let date = match words.len().cmp(&2) {
-            Ordering::Equal => words[1],
-            Ordering::Greater => words[2],
-            Ordering::Less => "Yesterday"
+
+    fn get_coin(&self, db: &db::DB, coin: String) -> String {
+        self.find_coin(db, &coin).unwrap_or_else(|| "bitcoin".to_string())
+    }
+
+    fn parse_period(&self, db: &db::DB, words: &[&str]) -> Result<(Vec<String>, Vec<String>, dates::Period)> {
+        let today = Utc::now().date_naive();
+        let (period, used) = match dates::parse(&words[1..], today) {
+            Some(p) => p,
+            None => match words.last() {
+                Some(w) if words.len() > 1 && dates::looks_like_date(w)
+                    && self.find_coin(db, &w.to_lowercase()).is_none() =>
+                    return Err(Error::Usage(format!("Invalid date: {}", w))),
+                _ => (dates::Period::Day(today - Duration::days(1)), 0)
+            }
         };
 
-        NaiveDate::from_str(date).unwrap_or(Utc::today().naive_local() - Duration::days(1))
+        let period = period.clamp(today).map_err(Error::Usage)?;
+        let (coins, ignored) = self.parse_coin_args(db, &words[..words.len() - used]);
+        Ok((coins, ignored, period))
+    }
+
+    fn parse_window(&self, words: &[&str]) -> Option<window::Window> {
+        words.iter().skip(1).rev().find_map(|w| window::Window::parse(w))
     }
 
-    fn parse_amount(&self, words: &[&str]) -> f32 {
+    fn parse_count(&self, words: &[&str], default: usize, max: usize) -> usize {
+        words.iter().skip(1).find_map(|w| usize::from_str(w).ok()).unwrap_or(default).clamp(1, max)
+    }
+
+    fn parse_amount(&self, words: &[&str]) -> Decimal {
         let amount = match words.len().cmp(&2) {
             Ordering::Equal => words[1],
             Ordering::Greater => words[2],
             Ordering::Less => "1.0"
         };
 
-        f32::from_str(amount).unwrap_or(1.0)
+        Decimal::from_str(amount).unwrap_or(Decimal::ONE)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(coin: &str) -> Option<String> {
+        match coin {
+            "btc" | "bitcoin" => Some("bitcoin".to_string()),
+            "eth" => Some("ethereum".to_string()),
+            "bch" => Some("bitcoin cash".to_string()),
+            "xrp" => Some("ripple".to_string()),
+            "ltc" => Some("litecoin".to_string()),
+            "doge" => Some("dogecoin".to_string()),
+            _ => None
+        }
+    }
+
+    macro_rules! pick_coins_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, coins, ignored): (&str, &[&str], &[&str]) = $value;
+                let words = input.split_whitespace().collect::<Vec<_>>();
+                assert_eq!((coins.iter().map(|c| c.to_string()).collect(), ignored.iter().map(|i| i.to_string()).collect()),
+                           pick_coins(&words, find));
+            }
+        )*
+        }
+    }
+
+    pick_coins_tests! {
+        pick_coins_0: ("", &["bitcoin"], &[]),
+        pick_coins_1: ("ETH", &["ethereum"], &[]),
+        pick_coins_2: ("btc eth btc bitcoin", &["bitcoin", "ethereum"], &[]),
+        pick_coins_3: ("btc eth bch xrp ltc doge", &["bitcoin", "ethereum", "bitcoin cash", "ripple", "litecoin"], &["doge"]),
+        pick_coins_4: ("btc fooo", &["bitcoin"], &["fooo"]),
+        pick_coins_5: ("fooo", &["bitcoin"], &["fooo"]),
+        pick_coins_6: ("eth 7d", &["ethereum"], &[]),
     }
-}
\ No newline at end of file
+}
@@ -1,12 +1,17 @@
 use std::fmt;
 use titlecase::titlecase;
 
-use super::formatter::format_change;
+use super::formatter::{format_age, format_change};
+use rooney::{movers, prices, window::Window};
 
 use super::{db, Command, CommandArgs, Error, Result};
 pub(super) struct Bulls;
 
 
+const MOVERS: usize = 3;
+const MAX_MOVERS: usize = 10;
+
+
 struct Mover {
     pub name: String,
     pub ticker: String,
@@ -15,29 +20,32 @@ struct Mover {
 
 
 pub struct Movers {
-    movers: Vec<Mover>
+    movers: Vec<Mover>,
+    window: Window,
+    // Seconds since the newest price was written, windows end there rather than now.
+    age: i64,
+}
+
+
+// Direction is 1.0 for the biggest gains first and -1.0 for the biggest losses first.
+fn query(db: &mut db::DB, window: Window, direction: f32, count: usize) -> Option<Movers> {
+    let movers = movers::query(db, window.unit, &window.interval, direction, count as i64, 0).unwrap();
+    if movers.is_empty() {
+        return None;
+    }
+    let age = prices::age(db).unwrap().unwrap_or(0);
+    Some(Movers {movers: movers.into_iter().map(|m| Mover {name: m.name, ticker: m.ticker, diff: m.change}).collect(), window, age})
 }
 
 
-impl Bulls {
-    fn query(&self, db: &mut db::DB) -> Option<Movers> {
-        let query =
-            "with movers as (
-                select distinct coin_id, first_value(euro) over w as first, last_value(euro) over w as last
-                from prices where time::date=(select max(time)::date from prices) WINDOW w as (
-                    partition by coin_id order by time range between unbounded preceding and unbounded
-                    following) order by coin_id
-            )
-            select name, ticker, first, last, cast((last-first)*100/first as real) as diff
-            from movers
-            join coins using(coin_id)
-            order by diff desc limit 3;";
-    
-        let rows = db.connection.query(query, &[]).unwrap();
-        if rows.len() < 3 {
-            return None;
-        }
-        Some(Movers {movers: rows.into_iter().map(|r| Mover {name: r.get(0), ticker: r.get(1), diff: r.get(4)}).collect::<Vec<Mover>>()})
+fn run<C: CommandArgs>(command: &C, db: &mut db::DB, msg: &Option<&str>, direction: f32) -> Result<String> {
+    let commands: Vec<&str> = msg.unwrap().split_whitespace().collect();
+    let window = command.parse_window(&commands).unwrap_or_else(Window::today);
+    let count = command.parse_count(&commands, MOVERS, MAX_MOVERS);
+
+    match query(db, window, direction, count) {
+        Some(ms) => Ok(ms.to_string()),
+        None => Err(Error::Contact)
     }
 }
 
@@ -47,17 +55,12 @@ impl Command for Bulls {
         "!bulls"
     }
 
-    fn run(&self, db: &mut db::DB, _: &Option<&str>) -> Result<String> {
-        let movers = self.query(db);
-
-        match movers {
-            Some(ms) => Ok(ms.to_string()),
-            None => Err(Error::Contact)
-        }
+    fn run(&self, db: &mut db::DB, msg: &Option<&str>) -> Result<String> {
+        run(self, db, msg, 1.0)
     }
 
     fn help(&self) -> &'static str {
-        "!bulls: Get today's big winners."
+        "!bulls [window] [count]: Get the big winners. Defaults to today's top 3, windows can be 1h, 24h, 7d, 30d or ytd."
     }
 }
 
@@ -67,45 +70,17 @@ impl CommandArgs for Bulls {}
 pub(super) struct Bears;
 
 
-impl Bears {
-    fn query(&self, db: &mut db::DB) -> Option<Movers> {
-        let query =
-            "with movers as (
-                select distinct coin_id, first_value(euro) over w as first, last_value(euro) over w as last
-                from prices where time::date=(select max(time)::date from prices) WINDOW w as (
-                    partition by coin_id order by time range between unbounded preceding and unbounded
-                    following) order by coin_id
-            )
-            select name, ticker, first, last, cast((last-first)*100/first as real) as diff
-            from movers
-            join coins using(coin_id)
-            order by diff asc limit 3;";
-    
-        let rows = db.connection.query(query, &[]).unwrap();
-        if rows.len() < 3 {
-            return None;
-        }
-        Some(Movers {movers: rows.into_iter().map(|r| Mover {name: r.get(0), ticker: r.get(1), diff: r.get(4)}).collect::<Vec<Mover>>()})
-    }
-}
-
-
 impl Command for Bears {
     fn name(&self) -> &'static str {
         "!bears"
     }
 
-    fn run(&self, db: &mut db::DB, _: &Option<&str>) -> Result<String> {
-        let movers = self.query(db);
-
-        match movers {
-            Some(ms) => Ok(ms.to_string()),
-            None => Err(Error::Contact)
-        }
+    fn run(&self, db: &mut db::DB, msg: &Option<&str>) -> Result<String> {
+        run(self, db, msg, -1.0)
     }
 
     fn help(&self) -> &'static str {
-        "!bears: Get today's big losers."
+        "!bears [window] [count]: Get the big losers. Defaults to today's top 3, windows can be 1h, 24h, 7d, 30d or ytd."
     }
 }
 
@@ -113,15 +88,10 @@ impl Command for Bears {
 impl CommandArgs for Bears {}
 
 
-impl fmt::Display for Mover {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} ({}) {} Today\x03", titlecase(&self.name), self.ticker.to_uppercase(), format_change(self.diff))
-    }
-}
-
-
 impl fmt::Display for Movers {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {} {}", self.movers[0], self.movers[1], self.movers[2])
+        let movers = self.movers.iter().map(|m| format!("{} ({}) {} {}\x03", titlecase(&m.name), m.ticker.to_uppercase(),
+                                                        format_change(m.diff), self.window.long));
+        write!(f, "{}{}", movers.collect::<Vec<_>>().join(" "), format_age(self.age))
     }
-}
\ No newline at end of file
+}
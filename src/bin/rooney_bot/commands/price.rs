@@ -1,6 +1,9 @@
 use std::fmt;
 use titlecase::titlecase;
-use super::{db, Command, CommandArgs, Error, formatter::format_change, formatter::format_currency, Result};
+use rooney::{prices, window::Window};
+use rust_decimal::Decimal;
+use super::{db, Command, CommandArgs, Error, Result, sort_by_coins, with_ignored};
+use super::formatter::{format_age, format_change, format_currency, is_stale};
 
 pub(super) struct Coin;
 pub(super) struct Coin24;
@@ -9,73 +12,62 @@ pub(super) struct Coin24;
 pub struct _Coin {
     pub name: String,
     pub ticker: String,
-    pub euro: f32,
-    pub dollar: f32,
-    pub min: f32,
-    pub max: f32,
+    pub euro: Decimal,
+    pub dollar: Decimal,
+    pub min: Decimal,
+    pub max: Decimal,
     pub change: f32,
-    pub median: f32
+    pub median: Decimal,
+    pub age: i64,
+    pub window: Window,
 }
 
 
 impl Coin {
-    pub fn query(&self, db: &mut db::DB, coin: &str) -> Option<_Coin> {
-        let query =
-            "with daily_prices as (
-                select * from prices
-                join coins using(coin_id)
-                where time >= current_date
-                and name = $1
-                order by time asc
-            ),
-            min_max_prices as (
-                select name, max(euro), min(euro)
-                from daily_prices
-                group by name
-            ),
-            all_prices as (
-                select row_number() over (partition by name order by time desc)
-                rn, name, ticker, euro, dollar, time
-                from daily_prices
-            ),
-            median_prices as (
-                select name, median(euro) as median
-                from all_prices
-                group by name
-            ),
-            latest_prices as (
-                select name, ticker, euro, dollar
-                from all_prices
-                where rn = 1
-            ),
-            first_price as (
-                select name, euro from daily_prices
-                where name=$1
-                limit 1
-            )
-            select name, ticker, cast(lp.euro as real), cast(dollar as real), cast(min as real), cast(max as real),
-            cast(((lp.euro - fp.euro)*100)/fp.euro as real), cast(median as real)
-            from latest_prices as lp
-            join min_max_prices using(name)
-            join first_price as fp using(name)
-            join median_prices using(name)";
-    
-        let rows = db.connection.query(query, &[&coin]).unwrap();
-        if rows.is_empty() {
-            return None;
+    pub fn query(&self, db: &mut db::DB, coins: &[String], window: &Window) -> Vec<_Coin> {
+        let prices = prices::summaries(db, coins, window.unit, &window.interval).unwrap()
+            .into_iter()
+            .map(|s| _Coin {
+                name: s.name,
+                ticker: s.ticker,
+                euro: s.euro,
+                dollar: s.dollar,
+                min: s.min,
+                max: s.max,
+                change: s.change,
+                median: s.median,
+                age: s.age,
+                window: window.clone(),
+            })
+            .collect();
+
+        sort_by_coins(coins, prices, |c| &c.name)
+    }
+
+    fn reply(&self, db: &mut db::DB, coins: &[String], window: Window) -> Result<String> {
+        let prices = self.query(db, coins, &window);
+
+        match prices.as_slice() {
+            [] => self.last_known(db, coins, &window),
+            [p] if coins.len() == 1 => Ok(p.to_string()),
+            ps => Ok(_Coin::compact(ps))
+        }
+    }
+
+    // Without prices in the window, such as when the updater has stalled for longer, the last known prices are given.
+    fn last_known(&self, db: &mut db::DB, coins: &[String], window: &Window) -> Result<String> {
+        let prices = sort_by_coins(coins, prices::last_known(db, coins).unwrap(), |p| &p.0.name);
+        let stale = prices.iter().any(|(_, age)| is_stale(*age));
+        let prices = prices.iter().map(|(p, age)| format!("{} ({}): €{} ${}{}", titlecase(&p.name), p.ticker.to_uppercase(),
+                                                          format_currency(p.euro), format_currency(p.dollar), format_age(*age)))
+                           .collect::<Vec<_>>();
+
+        match prices.as_slice() {
+            [] => Err(Error::Contact),
+            [p] if coins.len() == 1 => Ok(format!("{} price for {}, no prices for {}", label(stale), p,
+                                                  window.long.to_lowercase())),
+            ps => Ok(format!("{} prices: {}, no prices for {}", label(stale), ps.join(" | "), window.long.to_lowercase()))
         }
-    
-        let row = rows.get(0).unwrap();
-        Some(_Coin {
-            name: row.get(0),
-            ticker: row.get(1),
-            euro: row.get(2),
-            dollar: row.get(3),
-            min: row.get(4),
-            max: row.get(5),
-            change: row.get(6),
-            median: row.get(7),
-        })
     }
 }
 
@@ -87,17 +79,13 @@ impl Command for Coin {
 
     fn run(&self, db: &mut db::DB, msg: &Option<&str>) -> Result<String> {
         let commands: Vec<&str> = msg.unwrap().split_whitespace().collect();
-        let coin = self.get_coin(&db, self.parse_coin_arg(&commands));
-        let price = self.query(db, &coin);
-
-        match price {
-            Some(p) => Ok(p.display()),
-            None => Err(Error::Contact)
-        }
+        let (coins, ignored) = self.parse_coin_args(db, &commands);
+        with_ignored(self.reply(db, &coins, self.parse_window(&commands).unwrap_or_else(Window::today)), &ignored)
     }
 
     fn help(&self) -> &'static str {
-        "!coin [coin|ticker]: Get current price for a coin. Defaults to btc."
+        "!coin [coin|ticker ...] [window]: Get current price for up to 5 coins. Defaults to btc and today. \
+            Windows can be 1h, 24h, 7d, 30d, 3m, 1y or ytd."
     }
 }
 
@@ -105,68 +93,6 @@ impl Command for Coin {
 impl CommandArgs for Coin {}
 
 
-impl Coin24 {
-    pub fn query(&self, db: &mut db::DB, coin: &str) -> Option<_Coin> {
-        let query =
-            "with daily_prices as (
-                select * from prices
-                join coins using(coin_id)
-                where time >= now() - interval '24 hours'
-                and name = $1
-                order by time asc
-            ),
-            min_max_prices as (
-                select name, max(euro), min(euro)
-                from daily_prices
-                group by name
-            ),
-            all_prices as (
-                select row_number() over (partition by name order by time desc)
-                rn, name, ticker, euro, dollar, time
-                from daily_prices
-            ),
-            median_prices as (
-                select name, median(euro) as median
-                from all_prices
-                group by name
-            ),
-            latest_prices as (
-                select name, ticker, euro, dollar
-                from all_prices
-                where rn = 1
-            ),
-            first_price as (
-                select name, euro from daily_prices
-                where name=$1
-                limit 1
-            )
-            select name, ticker, cast(lp.euro as real), cast(dollar as real), cast(min as real), cast(max as real),
-            cast(((lp.euro - fp.euro)*100)/fp.euro as real), cast(median as real)
-            from latest_prices as lp
-            join min_max_prices using(name)
-            join first_price as fp using(name)
-            join median_prices using(name)";
-    
-        let rows = db.connection.query(query, &[&coin]).unwrap();
-        if rows.is_empty() {
-            return None;
-        }
-    
-        let row = rows.get(0).unwrap();
-        Some(_Coin {
-            name: row.get(0),
-            ticker: row.get(1),
-            euro: row.get(2),
-            dollar: row.get(3),
-            min: row.get(4),
-            max: row.get(5),
-            change: row.get(6),
-            median: row.get(7),
-        })
-    }
-}
-
-
 impl Command for Coin24 {
     fn name(&self) -> &'static str {
         "!coin24"
@@ -174,17 +100,13 @@ impl Command for Coin24 {
 
     fn run(&self, db: &mut db::DB, msg: &Option<&str>) -> Result<String> {
         let commands: Vec<&str> = msg.unwrap().split_whitespace().collect();
-        let coin = self.get_coin(&db, self.parse_coin_arg(&commands));
-        let price = self.query(db, &coin);
-
-        match price {
-            Some(p) => Ok(p.to_string()),
-            None => Err(Error::Contact)
-        }
+        let (coins, ignored) = self.parse_coin_args(db, &commands);
+        with_ignored(Coin.reply(db, &coins, self.parse_window(&commands).unwrap_or_else(Window::last_24_hours)), &ignored)
     }
 
     fn help(&self) -> &'static str {
-        "!coin24 [coin|ticker]: Get current price for a coin. Defaults to btc. Statistics for past 24 hours."
+        "!coin24 [coin|ticker ...] [window]: Get current price for up to 5 coins. Defaults to btc. \
+            Statistics for past 24 hours unless another window is given."
     }
 }
 
@@ -194,19 +116,32 @@ impl CommandArgs for Coin24 {}
 
 impl fmt::Display for _Coin {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Current price for {} ({}): €{} ${} 24h Low: €{} Median: €{} 24h High: €{} {} Last 24 hours",
-                    titlecase(&self.name), self.ticker.to_uppercase(), format_currency(self.euro),
-                    format_currency(self.dollar), format_currency(self.min), format_currency(self.median),
-                    format_currency(self.max), format_change(self.change))
+        write!(f, "{} price for {} ({}): €{} ${}{} {} Low: €{} Median: €{} {} High: €{} {} {}",
+                    label(is_stale(self.age)), titlecase(&self.name), self.ticker.to_uppercase(),
+                    format_currency(self.euro), format_currency(self.dollar), format_age(self.age), self.window.short, format_currency(self.min),
+                    format_currency(self.median), self.window.short, format_currency(self.max),
+                    format_change(self.change), self.window.long)
+    }
+}
+
+
+// Stale prices are labelled as the last known price rather than the current one.
+fn label(stale: bool) -> &'static str {
+    match stale {
+        true => "Last",
+        false => "Current"
     }
 }
 
 
 impl _Coin {
-    fn display(&self) -> String {
-        format!("Current price for {} ({}): €{} ${} Today's Low: €{} Median: €{} Today's High: €{} {} Today",
-                titlecase(&self.name), self.ticker.to_uppercase(), format_currency(self.euro),
-                format_currency(self.dollar), format_currency(self.min), format_currency(self.median),
-                format_currency(self.max), format_change(self.change))
+    fn compact(prices: &[_Coin]) -> String {
+        let period = &prices[0].window.long;
+        let stale = prices.iter().any(|p| is_stale(p.age));
+        let prices = prices.iter().map(|p| format!("{} ({}): €{} ${}{} {}\x03", titlecase(&p.name), p.ticker.to_uppercase(),
+                                                   format_currency(p.euro), format_currency(p.dollar),
+                                                   format_age(p.age), format_change(p.change)))
+                           .collect::<Vec<_>>();
+        format!("{} prices: {} {}", label(stale), prices.join(" | "), period)
     }
 }
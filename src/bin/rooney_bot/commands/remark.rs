@@ -30,13 +30,13 @@ impl Remark {
             offset floor(random() * (select count(*) from all_remarks))
             limit 1;";
     
-        let rows = db.connection.query(query, &[&msg]).unwrap();
+        let rows = db.query("remark", query, &[&msg]).unwrap();
     
         if rows.is_empty() {
             return None;
         }
     
-        rows.get(0).unwrap().get(0)
+        rows[0].get(0)
     }
 }
 
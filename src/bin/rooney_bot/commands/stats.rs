@@ -1,8 +1,11 @@
 use std::fmt;
 use titlecase::titlecase;
-use chrono::NaiveDate;
+use chrono::{Duration, NaiveDate, Utc};
+use rooney::stats;
+use rust_decimal::Decimal;
 
-use super::{db, Command, CommandArgs, Error, Result};
+use super::{db, Command, CommandArgs, Error, Result, sort_by_coins, with_ignored};
+use super::dates::Period;
 use super::formatter::format_currency;
 
 pub(super) struct Stats;
@@ -12,40 +15,81 @@ struct _Stats {
     pub name: String,
     pub ticker: String,
     pub date: NaiveDate,
-    pub min: f32,
-    pub average: f32,
-    pub median: f32,
-    pub std_dev: f32,
-    pub max: f32,
+    pub min: Decimal,
+    pub average: Decimal,
+    pub median: Decimal,
+    pub std_dev: Decimal,
+    pub max: Decimal,
+}
+
+
+struct _RangeStats {
+    name: String,
+    ticker: String,
+    start: NaiveDate,
+    end: NaiveDate,
+    min: Decimal,
+    average: Decimal,
+    max: Decimal,
+    days: i64,
 }
 
 
 impl Stats {
-    fn query(&self, db: &mut db::DB, coin: String, date: NaiveDate) -> Option<_Stats> {
+    fn query(&self, db: &mut db::DB, coins: &[String], date: NaiveDate) -> Vec<_Stats> {
+        let stats = stats::query(db, coins, date).unwrap()
+            .into_iter()
+            .map(|s| _Stats {
+                name: s.name,
+                ticker: s.ticker,
+                date: s.date,
+                min: s.min,
+                average: s.average,
+                median: s.median,
+                std_dev: s.std_dev,
+                max: s.max,
+            })
+            .collect();
+
+        sort_by_coins(coins, stats, |s| &s.name)
+    }
+
+    fn query_range(&self, db: &mut db::DB, coins: &[String], start: NaiveDate, end: NaiveDate) -> Vec<_RangeStats> {
         let query =
-            "select name, ticker, date, cast(min_euro as real), cast(average_euro as real), cast(median_euro as real),
-                cast(std_dev as real), cast(max_euro as real)
+            "select name, ticker, min(date), max(date), min(min_euro), avg(average_euro), max(max_euro), count(*)
                 from daily_stats
                 join coins using(coin_id)
-                where name = $1
-                and date = $2";
-        let rows = db.connection.query(query, &[&coin, &date]).unwrap();
-
-        if rows.is_empty() {
-            return None;
-        }
+                where name = any($1)
+                and date between $2 and $3
+                group by name, ticker";
+        let rows = db.query("daily_stats_range", query, &[&coins, &start, &end]).unwrap();
 
-        let row = rows.get(0).unwrap();
-        Some(_Stats{
+        sort_by_coins(coins, rows.iter().map(|row| _RangeStats {
             name: row.get(0),
             ticker: row.get(1),
-            date: row.get(2),
-            min: row.get(3),
-            average: row.get(4),
-            median: row.get(5),
-            std_dev: row.get(6),
-            max: row.get(7),
-        })
+            start: row.get(2),
+            end: row.get(3),
+            min: row.get(4),
+            average: row.get(5),
+            max: row.get(6),
+            days: row.get(7),
+        }).collect(), |s| &s.name)
+    }
+
+    fn day(&self, db: &mut db::DB, coins: &[String], date: NaiveDate) -> Result<String> {
+        match self.query(db, coins, date).as_slice() {
+            [] => Err(Error::Contact),
+            [s] if coins.len() == 1 => Ok(s.to_string()),
+            ss => Ok(_Stats::compact(ss, date))
+        }
+    }
+
+    fn range(&self, db: &mut db::DB, coins: &[String], start: NaiveDate, end: NaiveDate) -> Result<String> {
+        match self.query_range(db, coins, start, end).as_slice() {
+            [] => Err(Error::Contact),
+            [s] if coins.len() == 1 => Ok(s.to_string()),
+            ss => Ok(_RangeStats::compact(ss))
+        }
     }
 }
 
@@ -57,18 +101,18 @@ impl Command for Stats {
 
     fn run(&self, db: &mut db::DB, msg: &Option<&str>) -> Result<String> {
         let commands: Vec<&str> = msg.unwrap().split_whitespace().collect();
-        let coin = self.get_coin(db, self.parse_coin_arg(&commands));
-        let date = self.parse_date(&commands);
-        let stats = self.query(db, coin, date);
+        let (coins, ignored, period) = self.parse_period(db, &commands)?;
 
-        match stats {
-            Some(s) => Ok(s.to_string()),
-            None => Err(Error::Contact)
-        }
+        with_ignored(match period {
+            Period::Day(date) => self.day(db, &coins, date),
+            Period::Since(start) => self.range(db, &coins, start, Utc::now().date_naive() - Duration::days(1)),
+            Period::Range(start, end) => self.range(db, &coins, start, end),
+        }, &ignored)
     }
 
     fn help(&self) -> &'static str {
-        "!stats [coin|ticker] [date]: Get the statistics for a coin's price over the course of a day. \
+        "!stats [coin|ticker ...] [date|period]: Get the statistics for up to 5 coins' prices over the course of a day. \
+            Dates can be 2021-03-01, yesterday, monday or a period such as 7d, last week, 2021-03 or 2021-01-01 2021-06-01. \
             Defaults to btc and yesterday's date."
     }
 }
@@ -84,4 +128,35 @@ impl fmt::Display for _Stats {
                 format_currency(self.average), format_currency(self.std_dev),
                 format_currency(self.median), format_currency(self.max))
     }
-}
\ No newline at end of file
+}
+
+
+impl _Stats {
+    fn compact(stats: &[_Stats], date: NaiveDate) -> String {
+        let stats = stats.iter().map(|s| format!("{} ({}): Min €{} Mean €{} Max €{}", titlecase(&s.name),
+                                                 s.ticker.to_uppercase(), format_currency(s.min),
+                                                 format_currency(s.average), format_currency(s.max)))
+                         .collect::<Vec<_>>();
+        format!("Stats on {}: {}", date, stats.join(" | "))
+    }
+}
+
+
+impl fmt::Display for _RangeStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Stats for {} ({}) from {} to {} ({} days): Min €{} Mean €{} Max €{}",
+                titlecase(&self.name), self.ticker.to_uppercase(), self.start, self.end, self.days,
+                format_currency(self.min), format_currency(self.average), format_currency(self.max))
+    }
+}
+
+
+impl _RangeStats {
+    fn compact(stats: &[_RangeStats]) -> String {
+        let stats = stats.iter().map(|s| format!("{} ({}) from {} to {}: Min €{} Mean €{} Max €{}", titlecase(&s.name),
+                                                 s.ticker.to_uppercase(), s.start, s.end, format_currency(s.min),
+                                                 format_currency(s.average), format_currency(s.max)))
+                         .collect::<Vec<_>>();
+        format!("Stats: {}", stats.join(" | "))
+    }
+}
@@ -0,0 +1,167 @@
+use std::time::{Duration, Instant};
+
+
+const COLOUR: char = '\x03';
+const RESET: char = '\x0f';
+
+
+pub(super) struct Throttle {
+    burst: u32,
+    interval: Duration,
+    tokens: u32,
+    last_refill: Instant,
+}
+
+
+impl Throttle {
+    pub(super) fn new(burst: u32, interval: Duration) -> Self {
+        Self {
+            burst: burst.max(1),
+            interval,
+            tokens: burst.max(1),
+            last_refill: Instant::now(),
+        }
+    }
+
+    // Takes a token if one is available, otherwise returns how long to wait before asking again.
+    pub(super) fn wait(&mut self, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        let refilled = (elapsed.as_millis() / self.interval.as_millis().max(1)) as u32;
+
+        if refilled > 0 {
+            self.tokens = self.tokens.saturating_add(refilled).min(self.burst);
+            self.last_refill = match self.tokens {
+                t if t == self.burst => now,
+                _ => self.last_refill + self.interval * refilled
+            };
+        }
+
+        if self.tokens > 0 {
+            self.tokens -= 1;
+            return None;
+        }
+
+        Some(self.interval.checked_sub(now.saturating_duration_since(self.last_refill)).unwrap_or_default())
+    }
+}
+
+
+pub(super) fn split_message(message: &str, max_bytes: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut colour = String::new();
+    let mut prefix = 0;
+
+    for word in message.split_whitespace() {
+        if line.len() > prefix {
+            if line.len() + 1 + word.len() <= max_bytes {
+                line.push(' ');
+            } else {
+                lines.push(line);
+                line = colour.clone();
+                prefix = line.len();
+            }
+        }
+
+        for atom in atoms(word) {
+            if line.len() + atom.len() > max_bytes && line.len() > prefix {
+                lines.push(line);
+                line = colour.clone();
+                prefix = line.len();
+            }
+
+            if atom.starts_with(COLOUR) {
+                colour = match atom.len() {
+                    1 => String::new(),
+                    _ => atom.to_string()
+                };
+            } else if atom.starts_with(RESET) {
+                colour.clear();
+            }
+
+            line.push_str(atom);
+        }
+    }
+
+    if line.len() > prefix {
+        lines.push(line);
+    }
+
+    lines
+}
+
+
+// Splits a word into characters, keeping each mIRC colour code together as a single atom.
+fn atoms(word: &str) -> Vec<&str> {
+    let mut atoms = Vec::new();
+    let mut rest = word;
+
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            COLOUR => colour_code_len(rest),
+            _ => c.len_utf8()
+        };
+        atoms.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+
+    atoms
+}
+
+
+fn colour_code_len(code: &str) -> usize {
+    let bytes = code.as_bytes();
+    let digits = |start: usize| bytes.iter().skip(start).take(2).take_while(|b| b.is_ascii_digit()).count();
+
+    let foreground = digits(1);
+    let mut len = 1 + foreground;
+
+    if foreground > 0 && bytes.get(len) == Some(&b',') {
+        let background = digits(len + 1);
+        if background > 0 {
+            len += 1 + background;
+        }
+    }
+
+    len
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! split_message_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, max_bytes, expected) = $value;
+                assert_eq!(expected, split_message(input, max_bytes));
+            }
+        )*
+        }
+    }
+
+    split_message_tests! {
+        split_message_0: ("Short reply", 20, vec!["Short reply"]),
+        split_message_1: ("one two three four", 9, vec!["one two", "three", "four"]),
+        split_message_2: ("abcdefghij", 4, vec!["abcd", "efgh", "ij"]),
+        split_message_3: ("a \x0303Up: 1.00% Today\x03 b", 14, vec!["a \x0303Up: 1.00%", "\x0303Today\x03 b"]),
+        split_message_4: ("\x0305,01abc", 8, vec!["\x0305,01ab", "\x0305,01c"]),
+        split_message_5: ("€€€", 7, vec!["€€", "€"]),
+        split_message_6: ("", 10, Vec::<String>::new()),
+    }
+
+    #[test]
+    fn throttle_allows_burst_then_waits() {
+        let mut throttle = Throttle::new(2, Duration::from_secs(1));
+        let start = Instant::now();
+
+        assert_eq!(None, throttle.wait(start));
+        assert_eq!(None, throttle.wait(start));
+        assert!(throttle.wait(start).is_some());
+        assert_eq!(None, throttle.wait(start + Duration::from_secs(1)));
+        assert!(throttle.wait(start + Duration::from_secs(1)).is_some());
+    }
+}
@@ -1,23 +1,49 @@
-use crate::{commands, Error, Messenger, Result};
+use crate::{commands, flood, Error, Messenger, Result};
 
 use irc::client::prelude::*;
 use irc::error::IrcError;
 use log::{info, warn};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+
+const MAX_LINE_BYTES: usize = 512;
+// Room for the ":nick!user@host " prefix the server adds when relaying our messages.
+const HOSTMASK_BYTES: usize = 100;
+const FLOOD_BURST: u32 = 4;
+const FLOOD_INTERVAL_MS: u64 = 2000;
+
 
 impl Messenger for IrcClient {
     fn init(&self) -> Result<()> {
         Ok(self.identify()?)
     }
 
+    fn option(&self, name: &str) -> Option<String> {
+        self.config().get_option(name).map(|o| o.to_string())
+    }
+
     fn run(&self, mut handler: impl FnMut(&str) -> commands::Result<String>) -> Result<()> {
+        let outgoing = outgoing(self);
+
         Ok(self.for_each_incoming(|message| {
             if let Command::PRIVMSG(ref target, ref msg) = message.command {
-                match handler(&msg) {
-                    Ok(response) => {
-                        self.send_privmsg(message.response_target().unwrap(), &response)
-                            .unwrap_or_else(|e| warn!("{}", e))
+                let response = match handler(msg) {
+                    Ok(response) => Some(response),
+                    Err(commands::Error::Usage(reply)) => Some(reply),
+                    Err(e) => {
+                        warn!("{}: {}", target, e);
+                        None
+                    }
+                };
+
+                if let Some(response) = response {
+                    let response_target = message.response_target().unwrap();
+                    for line in flood::split_message(&response, max_message_bytes(response_target)) {
+                        outgoing.send((response_target.to_string(), line)).unwrap_or_else(|e| warn!("{}", e));
                     }
-                    Err(e) => warn!("{}: {}", target, e),
                 }
                 info!("{} said {} to {}", message.source_nickname().unwrap(), msg, target);
             }
@@ -25,6 +51,36 @@ impl Messenger for IrcClient {
     }
 }
 
+
+fn outgoing(client: &IrcClient) -> Sender<(String, String)> {
+    let (tx, rx) = mpsc::channel::<(String, String)>();
+    let sender = client.clone();
+    let mut throttle = flood::Throttle::new(option(client, "flood_burst", FLOOD_BURST),
+                                            Duration::from_millis(option(client, "flood_interval_ms", FLOOD_INTERVAL_MS)));
+
+    thread::spawn(move || {
+        for (target, line) in rx {
+            while let Some(wait) = throttle.wait(Instant::now()) {
+                thread::sleep(wait);
+            }
+            sender.send_privmsg(&target, &line).unwrap_or_else(|e| warn!("{}", e));
+        }
+    });
+
+    tx
+}
+
+
+fn option<T: FromStr>(client: &IrcClient, name: &str, default: T) -> T {
+    client.option(name).and_then(|o| o.parse().ok()).unwrap_or(default)
+}
+
+
+fn max_message_bytes(target: &str) -> usize {
+    MAX_LINE_BYTES - HOSTMASK_BYTES - format!("PRIVMSG {} :\r\n", target).len()
+}
+
+
 impl From<IrcError> for Error {
     fn from(e: IrcError) -> Error {
         Error::Messenger(Box::new(e))
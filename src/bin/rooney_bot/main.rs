@@ -1,13 +1,25 @@
+// failure's derive implements Fail in a named const block, which rustc flags and only lets a module allow.
+#[allow(non_local_definitions)]
 mod commands;
+mod flood;
 mod irc_handler;
 
-use failure::Fail;
+use lazy_static::lazy_static;
 use log::{error, info};
+use prometheus::{register_int_counter, IntCounter};
+use rooney::metrics;
 use std::{env, thread, time};
 
 
+lazy_static! {
+    static ref RECONNECTS: IntCounter = register_int_counter!(
+        "rooney_irc_reconnects_total", "Times the bot has reconnected to IRC."
+    ).unwrap();
+}
+
+
 fn bot(messenger: impl Messenger) -> Result<()> {
-    let mut commands = match commands::Commands::new() {
+    let mut commands = match commands::Commands::new(messenger.option("chart_url")) {
         Ok(c) => c,
         Err(_) => return Err(Error::Contact)
     };
@@ -19,18 +31,26 @@ fn bot(messenger: impl Messenger) -> Result<()> {
 
 trait Messenger {
     fn init(&self) -> Result<()>;
+    fn option(&self, name: &str) -> Option<String>;
     fn run(&self, handler: impl FnMut(&str) -> commands::Result<String>) -> Result<()>;
 }
 
 
-#[derive(Debug, Fail)]
-enum Error {
-    #[fail(display = "Messenger error {}", _0)]
-    Messenger(#[cause] Box<dyn Fail>),
-    #[fail(display = "Cannot contact database")]
-    Contact,
+#[allow(non_local_definitions)]
+mod error {
+    use failure::Fail;
+
+    #[derive(Debug, Fail)]
+    pub enum Error {
+        #[fail(display = "Messenger error {}", _0)]
+        Messenger(#[cause] Box<dyn Fail>),
+        #[fail(display = "Cannot contact database")]
+        Contact,
+    }
 }
 
+use error::Error;
+
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -49,14 +69,21 @@ fn main() -> Result<()> {
         }
     };
 
+    let config = irc::client::data::Config::load(config)?;
+    if let Some(address) = config.get_option("metrics_address") {
+        lazy_static::initialize(&RECONNECTS);
+        metrics::serve(address).map_err(|e| Error::Messenger(Box::new(e)))?;
+    }
+
     loop {
         info!("Starting bot");
 
-        match bot(irc::client::prelude::IrcClient::new(&config)?) {
+        match bot(irc::client::prelude::IrcClient::from_config(config.clone())?) {
             Ok(_) => (),
             Err(e) => error!("An unexpected error occurred: {}", e)
         };
 
         thread::sleep(five_mins);
+        RECONNECTS.inc();
     }
 }
@@ -0,0 +1,483 @@
+use std::{collections::HashMap, error::Error, fs, io::Read, thread};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rooney::{db, metrics, prices};
+
+use chrono::Utc;
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use postgres::binary_copy::BinaryCopyInWriter;
+use postgres::types::Type;
+use prometheus::{register_int_counter, register_int_counter_vec, register_int_gauge, IntCounter, IntCounterVec, IntGauge};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use toml::Value;
+
+mod metadata;
+mod retention;
+
+
+lazy_static! {
+    static ref CYCLES: IntCounterVec = register_int_counter_vec!(
+        "rooney_updater_cycles_total", "Update cycles by result.", &["result"]
+    ).unwrap();
+    static ref LAST_SUCCESS: IntGauge = register_int_gauge!(
+        "rooney_updater_last_success_timestamp_seconds", "Unix time of the last successful update."
+    ).unwrap();
+    static ref COINS: IntGauge = register_int_gauge!(
+        "rooney_updater_coins", "Coins seen in the last successful update."
+    ).unwrap();
+    static ref SKIPPED: IntCounter = register_int_counter!(
+        "rooney_updater_skipped_markets_total", "Markets skipped because their data was invalid."
+    ).unwrap();
+    static ref METADATA: IntCounterVec = register_int_counter_vec!(
+        "rooney_updater_metadata_updates_total", "Coin metadata updates by result.", &["result"]
+    ).unwrap();
+    static ref RETENTION: IntCounterVec = register_int_counter_vec!(
+        "rooney_updater_retention_runs_total", "Price retention runs by result.", &["result"]
+    ).unwrap();
+}
+
+
+// A price more than this many times higher or lower than the last stored one is treated as bad data.
+const MAX_JUMP: Decimal = Decimal::TEN;
+
+
+// Markets are kept as raw JSON so one malformed entry doesn't fail the whole feed. Prices are parsed from the
+// feed's digits straight into decimals so nothing is lost to floating point on the way to the database.
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Markets {
+    Markets: Vec<serde_json::Value>
+}
+
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Market {
+    Label: String,
+    Name: String,
+    Price_btc: Option<Decimal>,
+    Price_usd: Option<Decimal>,
+    Price_cny: Option<Decimal>,
+    Price_eur: Option<Decimal>,
+    Price_gbp: Option<Decimal>,
+    Price_rur: Option<Decimal>,
+    Volume_24h: Option<Decimal>,
+    Timestamp: Option<i64>
+}
+
+
+#[derive(Debug, PartialEq)]
+struct Quote {
+    name: String,
+    ticker: String,
+    euro: Decimal,
+    dollar: Decimal,
+}
+
+
+fn valid_price(price: Option<Decimal>) -> Option<Decimal> {
+    price.filter(|p| p.is_sign_positive() && !p.is_zero())
+}
+
+
+impl Market {
+    // Checks the market against the coin's last stored euro price.
+    fn validate(&self, previous: Option<Decimal>) -> Result<Quote, String> {
+        let name = self.Name.trim().to_lowercase();
+        let ticker = self.Label.split('/').next().unwrap_or_default().trim().to_lowercase();
+        if name.is_empty() || ticker.is_empty() {
+            return Err(format!("missing name or ticker in {:?}", self.Label));
+        }
+
+        let (euro, dollar) = match (valid_price(self.Price_eur), valid_price(self.Price_usd)) {
+            (Some(e), Some(d)) => (e, d),
+            _ => return Err(format!("invalid price for {}: €{:?} ${:?}", name, self.Price_eur, self.Price_usd))
+        };
+
+        if let Some(p) = previous.filter(|p| p.is_sign_positive() && !p.is_zero()) {
+            let jump = |a: Decimal, b: Decimal| a.checked_div(b).is_none_or(|r| r > MAX_JUMP);
+            if jump(euro, p) || jump(p, euro) {
+                return Err(format!("implausible jump for {} from €{} to €{}", name, p, euro));
+            }
+        }
+
+        Ok(Quote { name, ticker, euro, dollar })
+    }
+}
+
+
+fn read_config(path: &str) -> Value {
+    let toml_content = fs::read_to_string(path)
+                        .unwrap_or_else(|_| panic!("Unable to read updater config from: {}", path));
+    toml::from_str(&toml_content).unwrap_or_else(|_| panic!("Unable to parse TOML from {}", path))
+}
+
+
+// A setting in seconds from a config section, or its default.
+fn seconds(section: &Value, name: &str, default: u64) -> u64 {
+    section.get(name).and_then(|v| v.as_integer()).map_or(default, |v| v.max(0) as u64)
+}
+
+
+// Seconds until the next multiple of `interval` since the epoch so updates land on wall-clock boundaries.
+fn until_next_tick(now: u64, interval: u64) -> u64 {
+    interval - now % interval
+}
+
+
+fn backoff(base: u64, attempt: u32) -> Duration {
+    Duration::from_secs(base.saturating_mul(2u64.saturating_pow(attempt)))
+}
+
+
+fn get_json(client: &reqwest::blocking::Client, url: &str) -> Result<String, Box<dyn Error>> {
+    let mut res = client.get(url).send()?.error_for_status()?;
+    let mut body = String::new();
+
+    res.read_to_string(&mut body)?;
+    Ok(body)
+}
+
+
+fn skip(reason: &str) {
+    warn!("Skipping market: {}", reason);
+    SKIPPED.inc();
+}
+
+
+fn parse_json(body: &str) -> Result<Vec<Market>, Box<dyn Error>> {
+    let json: Markets = serde_json::from_str(body)?;
+    Ok(json.Markets.into_iter().filter_map(|m| match serde_json::from_value(m) {
+        Ok(market) => Some(market),
+        Err(e) => {
+            skip(&e.to_string());
+            None
+        }
+    }).collect())
+}
+
+
+fn validate(db: &mut db::DB, markets: &[Market]) -> Result<Vec<Quote>, Box<dyn Error>> {
+    let names = markets.iter().map(|m| m.Name.trim().to_lowercase()).collect::<Vec<_>>();
+    let previous = prices::latest(db, &names)?.into_iter().map(|p| (p.name, p.euro)).collect::<HashMap<_, _>>();
+
+    Ok(markets.iter().zip(names).filter_map(|(m, name)| match m.validate(previous.get(&name).copied()) {
+        Ok(q) => Some(q),
+        Err(e) => {
+            skip(&e);
+            None
+        }
+    }).collect())
+}
+
+
+fn add_coins(db: &mut db::DB, quotes: &[Quote]) -> Result<(), Box<dyn Error>> {
+    let _timer = metrics::DB_QUERY_SECONDS.with_label_values(&["add_coins"]).start_timer();
+    let mut transaction = db.connection.transaction()?;
+    transaction.batch_execute("Create temporary table temp_coins(name varchar(255), ticker varchar(100)) on commit drop")?;
+
+    let sink = transaction.copy_in("Copy temp_coins(name, ticker) from stdin (format binary)")?;
+    let mut writer = BinaryCopyInWriter::new(sink, &[Type::VARCHAR, Type::VARCHAR]);
+    for q in quotes {
+        writer.write(&[&q.name, &q.ticker])?;
+    }
+    writer.finish()?;
+
+    transaction.batch_execute("Insert into coins(name, ticker) select tc.name, tc.ticker from temp_coins tc left join coins c using(name) where c.name is null")?;
+    transaction.commit()?;
+    Ok(())
+}
+
+
+fn coins_ids(db: &mut db::DB) -> Result<HashMap<String, i32>, String> {
+    match db.query("coins_ids", "Select name, coin_id from coins;", &[]) {
+        Ok(rows) => Ok(rows.iter().map(|r| (r.get::<usize, String>(0).to_lowercase(), r.get(1))).collect::<HashMap<_, _>>()),
+        Err(e) => Err(format!("Unable to find name/coin_id mappings {}", e))
+    }
+}
+
+
+// Prices are streamed in a single binary COPY, with euro and dollar encoded directly as numeric.
+fn update_prices(db: &mut db::DB, coins_ids: HashMap<String, i32>, quotes: &[Quote]) -> Result<usize, Box<dyn Error>> {
+    let _timer = metrics::DB_QUERY_SECONDS.with_label_values(&["update_prices"]).start_timer();
+    let mut transaction = db.connection.transaction()?;
+    let sink = transaction.copy_in("Copy prices(coin_id, euro, dollar) from stdin (format binary)")?;
+    let mut writer = BinaryCopyInWriter::new(sink, &[Type::INT4, Type::NUMERIC, Type::NUMERIC]);
+
+    for q in quotes {
+        let coin_id = match coins_ids.get(&q.name) {
+            Some(id) => id,
+            None => {
+                skip(&format!("no coin_id for {}", q.name));
+                continue;
+            }
+        };
+        writer.write(&[coin_id, &q.euro, &q.dollar])?;
+    }
+
+    let stored = writer.finish()? as usize;
+    transaction.execute("Select pg_notify($1, '')", &[&prices::CHANNEL])?;
+    transaction.commit()?;
+    Ok(stored)
+}
+
+
+// Returns the number of coins updated.
+fn get_updates(client: &reqwest::blocking::Client, url: &str) -> Result<usize, String> {
+    let json = match get_json(client, url) {
+        Ok(j) => j,
+        Err(e) => return Err(format!("Unable to get JSON: {}", e))
+    };
+
+    let markets = match parse_json(&json) {
+        Ok(m) => m,
+        Err(e) => return Err(format!("Unable to parse JSON: {}", e))
+    };
+
+    let mut db = match db::DB::new() {
+        Ok(db) => db,
+        Err(e) => return Err(format!("Unable to access DB: {}", e))
+    };
+
+    let quotes = match validate(&mut db, &markets) {
+        Ok(q) => q,
+        Err(e) => return Err(format!("Unable to validate markets: {}", e))
+    };
+
+    match add_coins(&mut db, &quotes) {
+        Ok(()) => (),
+        Err(e) => return Err(format!("Unable to add coins: {}", e))
+    };
+
+    let coins_ids = coins_ids(&mut db)?;
+    match update_prices(&mut db, coins_ids, &quotes) {
+        Ok(stored) => Ok(stored),
+        Err(e) => Err(format!("Unable to update prices: {}", e))
+    }
+}
+
+
+fn update(client: &reqwest::blocking::Client, url: &str, retries: u32, base_backoff: u64) -> Result<usize, String> {
+    let mut attempt = 0;
+    loop {
+        match get_updates(client, url) {
+            Err(e) if attempt < retries => {
+                let wait = backoff(base_backoff, attempt);
+                warn!("Update failed, retrying in {}s: {}", wait.as_secs(), e);
+                thread::sleep(wait);
+                attempt += 1;
+            },
+            result => return result
+        }
+    }
+}
+
+
+fn update_metadata(client: &reqwest::blocking::Client, source: &dyn metadata::Source) -> Result<u64, String> {
+    let metadata = match source.fetch(client) {
+        Ok(m) => m,
+        Err(e) => return Err(format!("Unable to get metadata from {}: {}", source.name(), e))
+    };
+
+    let mut db = match db::DB::new() {
+        Ok(db) => db,
+        Err(e) => return Err(format!("Unable to access DB: {}", e))
+    };
+
+    match metadata::store(&mut db, &metadata) {
+        Ok(updated) => Ok(updated),
+        Err(e) => Err(format!("Unable to store metadata: {}", e))
+    }
+}
+
+
+fn apply_retention(policy: &retention::Policy) -> Result<retention::Run, String> {
+    let mut db = match db::DB::new() {
+        Ok(db) => db,
+        Err(e) => return Err(format!("Unable to access DB: {}", e))
+    };
+
+    match retention::run(&mut db, policy) {
+        Ok(run) => Ok(run),
+        Err(e) => Err(format!("Unable to apply price retention: {}", e))
+    }
+}
+
+
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+    let config = read_config("configuration/Updater.toml");
+    let key = config["updater"]["api_key"].as_str().expect("Updater.toml must include an api_key field in the updater section.");
+    let url = format!("https://www.worldcoinindex.com/apiservice/json?key={}", key);
+    let settings = &config["updater"];
+    let interval = seconds(settings, "poll_interval_seconds", 5 * 60).max(1);
+    let retries = seconds(settings, "retries", 3) as u32;
+    let base_backoff = seconds(settings, "backoff_seconds", 5);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(seconds(settings, "http_timeout_seconds", 30)))
+        .build()?;
+    let source = metadata::source(&config)?;
+    let metadata_interval = config.get("metadata").map_or(0, |m| seconds(m, "interval_seconds", 60 * 60));
+    let mut next_metadata = Instant::now();
+    let policy = retention::policy(&config)?;
+    let retention_interval = config.get("retention").map_or(0, |r| seconds(r, "interval_seconds", 24 * 60 * 60));
+    let mut next_retention = Instant::now();
+    if let Some(address) = config["updater"].get("metrics_address").and_then(|a| a.as_str()) {
+        lazy_static::initialize(&CYCLES);
+        lazy_static::initialize(&LAST_SUCCESS);
+        lazy_static::initialize(&COINS);
+        lazy_static::initialize(&SKIPPED);
+        lazy_static::initialize(&METADATA);
+        lazy_static::initialize(&RETENTION);
+        metrics::serve(address)?;
+    }
+
+    loop {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        thread::sleep(Duration::from_secs(until_next_tick(now, interval)));
+        info!("Get updated price");
+
+        match update(&client, &url, retries, base_backoff) {
+            Ok(coins) => {
+                CYCLES.with_label_values(&["success"]).inc();
+                LAST_SUCCESS.set(Utc::now().timestamp());
+                COINS.set(coins as i64);
+            },
+            Err(e) => {
+                CYCLES.with_label_values(&["failure"]).inc();
+                error!("An unexpected error occurred: {}", e)
+            }
+        };
+
+        // Metadata changes slowly so it has its own interval, a failed refresh is retried on the next tick.
+        if let Some(s) = source.as_deref().filter(|_| Instant::now() >= next_metadata) {
+            match update_metadata(&client, s) {
+                Ok(updated) => {
+                    METADATA.with_label_values(&["success"]).inc();
+                    info!("Updated metadata for {} coins", updated);
+                    next_metadata = Instant::now() + Duration::from_secs(metadata_interval);
+                },
+                Err(e) => {
+                    METADATA.with_label_values(&["failure"]).inc();
+                    error!("{}", e)
+                }
+            }
+        }
+
+        // A backlog of old prices is worked through a few days each tick before waiting for the next interval.
+        if let Some(p) = policy.as_ref().filter(|_| Instant::now() >= next_retention) {
+            match apply_retention(p) {
+                Ok(run) => {
+                    RETENTION.with_label_values(&["success"]).inc();
+                    info!("Downsampled {} days, {} prices into {} hourly candles, rolled {} older ones into {} daily candles",
+                          run.days, run.downsampled, run.candles, run.pruned, run.daily_candles);
+                    if run.caught_up {
+                        next_retention = Instant::now() + Duration::from_secs(retention_interval);
+                    }
+                },
+                Err(e) => {
+                    RETENTION.with_label_values(&["failure"]).inc();
+                    error!("{}", e)
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_tick() {
+        assert_eq!(300, until_next_tick(1_600_000_200, 300));
+        assert_eq!(100, until_next_tick(1_600_000_100, 300));
+        assert_eq!(1, until_next_tick(59, 60));
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        assert_eq!(Duration::from_secs(5), backoff(5, 0));
+        assert_eq!(Duration::from_secs(20), backoff(5, 2));
+        assert_eq!(Duration::from_secs(u64::MAX), backoff(5, 80));
+    }
+
+    fn market(eur: Option<&str>, usd: Option<&str>) -> Market {
+        Market {
+            Label: "BTC/BTC".to_string(),
+            Name: "Bitcoin".to_string(),
+            Price_btc: Some(Decimal::ONE),
+            Price_usd: usd.map(|u| u.parse().unwrap()),
+            Price_cny: None,
+            Price_eur: eur.map(|e| e.parse().unwrap()),
+            Price_gbp: None,
+            Price_rur: None,
+            Volume_24h: None,
+            Timestamp: None,
+        }
+    }
+
+    macro_rules! validate_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (eur, usd, previous, valid): (_, _, Option<&str>, _) = $value;
+                assert_eq!(valid, market(eur, usd).validate(previous.map(|p| p.parse().unwrap())).is_ok());
+            }
+        )*
+        }
+    }
+
+    validate_tests! {
+        validate_0: (Some("30000"), Some("36000"), None, true),
+        validate_1: (Some("30000"), Some("36000"), Some("29000"), true),
+        validate_2: (None, Some("36000"), None, false),
+        validate_3: (Some("0"), Some("36000"), None, false),
+        validate_4: (Some("-1"), Some("36000"), None, false),
+        validate_5: (Some("0.00000001"), Some("0.00000002"), Some("0.00000001"), true),
+        validate_6: (Some("30000"), Some("0"), None, false),
+        validate_7: (Some("300000"), Some("360000"), Some("29000"), false),
+        validate_8: (Some("2000"), Some("2400"), Some("29000"), false),
+        validate_9: (Some("1"), Some("1"), Some("0.0000000000000000000000000001"), false),
+    }
+
+    #[test]
+    fn parse_skips_malformed_markets() {
+        let body = r#"{"Markets": [
+            {"Label": "BTC/BTC", "Name": "Bitcoin", "Price_eur": 30000.0, "Price_usd": 36000.0},
+            {"Label": "ETH/BTC", "Name": "Ethereum", "Price_eur": "n/a", "Price_usd": 2400.0},
+            {"Label": "LTC/BTC", "Price_eur": 150.0},
+            {"Label": "DOGE/BTC", "Name": "Dogecoin", "Price_eur": null, "Price_usd": null}
+        ]}"#;
+
+        let markets = parse_json(body).unwrap();
+        assert_eq!(vec!["Bitcoin", "Dogecoin"], markets.iter().map(|m| m.Name.as_str()).collect::<Vec<_>>());
+        assert_eq!(Ok(Quote { name: "bitcoin".to_string(), ticker: "btc".to_string(), euro: "30000.0".parse().unwrap(),
+                             dollar: "36000.0".parse().unwrap() }),
+                   markets[0].validate(None));
+        assert!(markets[1].validate(None).is_err());
+    }
+
+    #[test]
+    fn parse_exact_prices() {
+        let body = r#"{"Markets": [
+            {"Label": "BTC/BTC", "Name": "Bitcoin", "Price_eur": 43251.37, "Price_usd": 51234567.891234},
+            {"Label": "SHIB/BTC", "Name": "Shiba Inu", "Price_eur": 0.0000071234, "Price_usd": 8.1e-6}
+        ]}"#;
+
+        let quotes = parse_json(body).unwrap().iter().map(|m| m.validate(None).unwrap()).collect::<Vec<_>>();
+        assert_eq!(("43251.37", "51234567.891234"), (quotes[0].euro.to_string().as_str(), quotes[0].dollar.to_string().as_str()));
+        assert_eq!(("0.0000071234", "0.0000081"), (quotes[1].euro.to_string().as_str(), quotes[1].dollar.to_string().as_str()));
+    }
+
+    #[test]
+    fn settings() {
+        let config: Value = toml::from_str("[updater]\npoll_interval_seconds = 60").unwrap();
+        assert_eq!(60, seconds(&config["updater"], "poll_interval_seconds", 300));
+        assert_eq!(30, seconds(&config["updater"], "http_timeout_seconds", 30));
+    }
+}
@@ -0,0 +1,184 @@
+use std::error::Error;
+
+use log::warn;
+use postgres::binary_copy::BinaryCopyInWriter;
+use postgres::types::Type;
+use rooney::{coins, db, metrics};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use toml::Value;
+
+
+const COINGECKO_URL: &str = "https://api.coingecko.com/api/v3/coins/markets";
+const COINGECKO_PAGE_SIZE: u32 = 250;
+
+
+// Market data for a coin as reported by a metadata source. Amounts are in euro.
+#[derive(Debug, PartialEq)]
+pub struct Metadata {
+    pub name: String,
+    pub rank: Option<i32>,
+    pub market_cap: Option<Decimal>,
+    pub volume_24h: Option<Decimal>,
+    pub circulating_supply: Option<Decimal>,
+    pub max_supply: Option<Decimal>,
+}
+
+
+pub trait Source {
+    fn name(&self) -> &'static str;
+    fn fetch(&self, client: &reqwest::blocking::Client) -> Result<Vec<Metadata>, Box<dyn Error>>;
+}
+
+
+// The coins/markets listing, ordered by market cap so `pages` bounds how many of the largest coins are fetched.
+pub struct CoinGecko {
+    pages: u32,
+    api_key: Option<String>,
+}
+
+
+#[derive(Deserialize)]
+struct CoinGeckoMarket {
+    name: String,
+    market_cap_rank: Option<i32>,
+    market_cap: Option<Decimal>,
+    total_volume: Option<Decimal>,
+    circulating_supply: Option<Decimal>,
+    max_supply: Option<Decimal>,
+}
+
+
+impl CoinGecko {
+    fn parse(body: &str) -> Result<Vec<Metadata>, Box<dyn Error>> {
+        let markets: Vec<serde_json::Value> = serde_json::from_str(body)?;
+        Ok(markets.into_iter().filter_map(|m| match serde_json::from_value::<CoinGeckoMarket>(m) {
+            Ok(m) => Some(Metadata {
+                name: m.name.trim().to_lowercase(),
+                rank: m.market_cap_rank,
+                market_cap: m.market_cap,
+                volume_24h: m.total_volume,
+                circulating_supply: m.circulating_supply,
+                max_supply: m.max_supply,
+            }),
+            Err(e) => {
+                warn!("Skipping coin metadata: {}", e);
+                None
+            }
+        }).collect())
+    }
+}
+
+
+impl Source for CoinGecko {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    fn fetch(&self, client: &reqwest::blocking::Client) -> Result<Vec<Metadata>, Box<dyn Error>> {
+        let mut metadata = Vec::new();
+        for page in 1..=self.pages {
+            let url = format!("{}?vs_currency=eur&order=market_cap_desc&per_page={}&page={}", COINGECKO_URL,
+                              COINGECKO_PAGE_SIZE, page);
+            let mut request = client.get(&url);
+            if let Some(key) = &self.api_key {
+                request = request.header("x-cg-demo-api-key", key);
+            }
+
+            let page = CoinGecko::parse(&request.send()?.error_for_status()?.text()?)?;
+            let last = page.len() < COINGECKO_PAGE_SIZE as usize;
+            metadata.extend(page);
+            if last {
+                break;
+            }
+        }
+
+        Ok(metadata)
+    }
+}
+
+
+// The source configured in the [metadata] section, None when the section is missing.
+pub fn source(config: &Value) -> Result<Option<Box<dyn Source>>, String> {
+    let metadata = match config.get("metadata") {
+        Some(m) => m,
+        None => return Ok(None)
+    };
+
+    match metadata.get("source").and_then(|s| s.as_str()) {
+        Some("coingecko") => Ok(Some(Box::new(CoinGecko {
+            pages: metadata.get("pages").and_then(|p| p.as_integer()).map_or(4, |p| p.max(1) as u32),
+            api_key: metadata.get("api_key").and_then(|k| k.as_str()).map(String::from),
+        }))),
+        Some(s) => Err(format!("Unknown metadata source: {}", s)),
+        None => Err("The metadata section must include a source".to_string())
+    }
+}
+
+
+// Upserts metadata for coins we already track, matched on name. Returns the number of coins updated.
+pub fn store(db: &mut db::DB, metadata: &[Metadata]) -> Result<u64, Box<dyn Error>> {
+    coins::create_metadata_table(db)?;
+
+    let _timer = metrics::DB_QUERY_SECONDS.with_label_values(&["store_metadata"]).start_timer();
+    let mut transaction = db.connection.transaction()?;
+    transaction.batch_execute(
+        "Create temporary table temp_metadata(name varchar(255), rank integer, market_cap numeric, volume_24h numeric,
+        circulating_supply numeric, max_supply numeric) on commit drop")?;
+
+    let sink = transaction.copy_in("Copy temp_metadata from stdin (format binary)")?;
+    let mut writer = BinaryCopyInWriter::new(sink, &[Type::VARCHAR, Type::INT4, Type::NUMERIC, Type::NUMERIC,
+                                                     Type::NUMERIC, Type::NUMERIC]);
+    for m in metadata {
+        writer.write(&[&m.name, &m.rank, &m.market_cap, &m.volume_24h, &m.circulating_supply, &m.max_supply])?;
+    }
+    writer.finish()?;
+
+    let updated = transaction.execute(
+        "Insert into coin_metadata(coin_id, rank, market_cap, volume_24h, circulating_supply, max_supply, updated)
+        select distinct on (coin_id) coin_id, rank, market_cap, volume_24h, circulating_supply, max_supply, now()
+        from temp_metadata
+        join coins using(name)
+        order by coin_id, rank asc nulls last
+        on conflict (coin_id) do update set rank = excluded.rank, market_cap = excluded.market_cap,
+        volume_24h = excluded.volume_24h, circulating_supply = excluded.circulating_supply,
+        max_supply = excluded.max_supply, updated = excluded.updated", &[])?;
+    transaction.commit()?;
+    Ok(updated)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_coingecko() {
+        let body = r#"[
+            {"id": "bitcoin", "symbol": "btc", "name": "Bitcoin", "market_cap": 590123456789, "market_cap_rank": 1,
+             "total_volume": 25100000000.5, "circulating_supply": 19520000.0, "max_supply": 21000000.0},
+            {"id": "ethereum", "symbol": "eth", "name": "Ethereum", "market_cap": 240000000000, "market_cap_rank": 2,
+             "total_volume": null, "circulating_supply": 120270000.0, "max_supply": null},
+            {"id": "broken", "symbol": "brk", "name": null}
+        ]"#;
+
+        let metadata = CoinGecko::parse(body).unwrap();
+        assert_eq!(2, metadata.len());
+        assert_eq!(Metadata {
+            name: "bitcoin".to_string(),
+            rank: Some(1),
+            market_cap: Some(Decimal::new(590123456789, 0)),
+            volume_24h: Some(Decimal::new(251000000005, 1)),
+            circulating_supply: Some(Decimal::new(19520000, 0)),
+            max_supply: Some(Decimal::new(21000000, 0)),
+        }, metadata[0]);
+        assert_eq!((None, None), (metadata[1].volume_24h, metadata[1].max_supply));
+    }
+
+    #[test]
+    fn configured_source() {
+        assert!(source(&toml::from_str("[updater]").unwrap()).unwrap().is_none());
+        assert_eq!("coingecko", source(&toml::from_str("[metadata]\nsource = \"coingecko\"").unwrap()).unwrap().unwrap().name());
+        assert!(source(&toml::from_str("[metadata]\nsource = \"other\"").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use std::error::Error;
+
+use chrono::NaiveDate;
+use rooney::{candles, db, metrics};
+use toml::Value;
+
+
+// How long prices are kept at each resolution, from the [retention] section.
+#[derive(Debug, PartialEq)]
+pub struct Policy {
+    full_resolution_days: i32,
+    hourly_days: Option<i32>,
+    days_per_run: i32,
+}
+
+
+pub struct Run {
+    pub days: u32,
+    pub downsampled: u64,
+    pub candles: u64,
+    pub daily_candles: u64,
+    pub pruned: u64,
+    pub caught_up: bool,
+}
+
+
+fn days(section: &Value, name: &str) -> Option<i32> {
+    section.get(name).and_then(|d| d.as_integer()).map(|d| d.clamp(1, i32::MAX as i64) as i32)
+}
+
+
+// The policy configured in the [retention] section, None when the section is missing.
+pub fn policy(config: &Value) -> Result<Option<Policy>, String> {
+    let retention = match config.get("retention") {
+        Some(r) => r,
+        None => return Ok(None)
+    };
+
+    let policy = Policy {
+        full_resolution_days: days(retention, "full_resolution_days").unwrap_or(30),
+        hourly_days: days(retention, "hourly_days"),
+        days_per_run: days(retention, "days_per_run").unwrap_or(7),
+    };
+    match policy.hourly_days {
+        Some(h) if h < policy.full_resolution_days => Err("hourly_days must be at least full_resolution_days".to_string()),
+        _ => Ok(Some(policy))
+    }
+}
+
+
+// Replaces a day of prices with hourly candles. The day's daily_stats are added when missing and widened to the day's
+// true low and high otherwise, so all time lows and highs survive the raw prices. Returns the prices and candles written.
+fn downsample(db: &mut db::DB, day: NaiveDate) -> Result<(u64, u64), Box<dyn Error>> {
+    let _timer = metrics::DB_QUERY_SECONDS.with_label_values(&["downsample_prices"]).start_timer();
+    let mut transaction = db.connection.transaction()?;
+
+    transaction.execute(
+        "Update daily_stats d set min_euro = least(d.min_euro, t.min_euro), max_euro = greatest(d.max_euro, t.max_euro)
+        from (
+            select coin_id, min(euro) as min_euro, max(euro) as max_euro
+            from prices
+            where time >= $1::date and time < $1::date + 1
+            group by coin_id
+        ) t
+        where d.coin_id = t.coin_id
+        and d.date = $1
+        and (d.min_euro, d.max_euro) is distinct from (least(d.min_euro, t.min_euro), greatest(d.max_euro, t.max_euro))",
+        &[&day])?;
+
+    transaction.execute(
+        "Insert into daily_stats(coin_id, date, min_euro, average_euro, median_euro, std_dev, max_euro)
+        select coin_id, $1, min(euro), avg(euro),
+        ((array_agg(euro order by euro))[(count(*)::int + 1) / 2]
+         + (array_agg(euro order by euro))[(count(*)::int + 2) / 2]) / 2,
+        coalesce(stddev_pop(euro), 0), max(euro)
+        from prices p
+        where time >= $1::date and time < $1::date + 1
+        and not exists (select 1 from daily_stats d where d.coin_id = p.coin_id and d.date = $1)
+        group by coin_id", &[&day])?;
+
+    let candles = transaction.execute(
+        "Insert into hourly_candles
+        select coin_id, date_trunc('hour', time),
+        (array_agg(euro order by time asc))[1], max(euro), min(euro), (array_agg(euro order by time desc))[1],
+        (array_agg(dollar order by time asc))[1], max(dollar), min(dollar), (array_agg(dollar order by time desc))[1]
+        from prices
+        where time >= $1::date and time < $1::date + 1
+        group by coin_id, date_trunc('hour', time)
+        on conflict (coin_id, time) do update set
+        high_euro = greatest(hourly_candles.high_euro, excluded.high_euro),
+        low_euro = least(hourly_candles.low_euro, excluded.low_euro),
+        high_dollar = greatest(hourly_candles.high_dollar, excluded.high_dollar),
+        low_dollar = least(hourly_candles.low_dollar, excluded.low_dollar)", &[&day])?;
+
+    let downsampled = transaction.execute("Delete from prices where time >= $1::date and time < $1::date + 1", &[&day])?;
+    transaction.commit()?;
+    Ok((downsampled, candles))
+}
+
+
+// Rolls hourly candles past `hourly_days` into daily candles, in euro and dollar, and drops them. Returns the daily
+// candles written and the hourly candles dropped.
+fn roll_up(db: &mut db::DB, hourly_days: i32) -> Result<(u64, u64), Box<dyn Error>> {
+    let _timer = metrics::DB_QUERY_SECONDS.with_label_values(&["roll_up_hourly_candles"]).start_timer();
+    let mut transaction = db.connection.transaction()?;
+
+    let daily = transaction.execute(
+        "Insert into daily_candles
+        select coin_id, time::date,
+        (array_agg(open_euro order by time asc))[1], max(high_euro), min(low_euro),
+        (array_agg(close_euro order by time desc))[1],
+        (array_agg(open_dollar order by time asc))[1], max(high_dollar), min(low_dollar),
+        (array_agg(close_dollar order by time desc))[1]
+        from hourly_candles
+        where time < now()::date - $1::int
+        group by coin_id, time::date
+        on conflict (coin_id, date) do update set
+        high_euro = greatest(daily_candles.high_euro, excluded.high_euro),
+        low_euro = least(daily_candles.low_euro, excluded.low_euro),
+        high_dollar = greatest(daily_candles.high_dollar, excluded.high_dollar),
+        low_dollar = least(daily_candles.low_dollar, excluded.low_dollar)", &[&hourly_days])?;
+
+    let pruned = transaction.execute("Delete from hourly_candles where time < now()::date - $1::int", &[&hourly_days])?;
+    transaction.commit()?;
+    Ok((daily, pruned))
+}
+
+
+// Downsamples the oldest days past the full resolution window, at most `days_per_run` of them so a backlog doesn't
+// hold up price updates, then rolls hourly candles past their window into daily candles.
+pub fn run(db: &mut db::DB, policy: &Policy) -> Result<Run, Box<dyn Error>> {
+    candles::create_tables(db)?;
+    let mut run = Run { days: 0, downsampled: 0, candles: 0, daily_candles: 0, pruned: 0, caught_up: false };
+
+    while run.days < policy.days_per_run as u32 {
+        let rows = db.query("retention_oldest_day", "Select min(time)::date from prices where time < now()::date - $1::int",
+                            &[&policy.full_resolution_days])?;
+        let day = match rows[0].get::<_, Option<NaiveDate>>(0) {
+            Some(d) => d,
+            None => break
+        };
+
+        let (downsampled, candles) = downsample(db, day)?;
+        run.days += 1;
+        run.downsampled += downsampled;
+        run.candles += candles;
+    }
+    run.caught_up = run.days < policy.days_per_run as u32;
+
+    if let Some(hourly_days) = policy.hourly_days {
+        let (daily_candles, pruned) = roll_up(db, hourly_days)?;
+        run.daily_candles = daily_candles;
+        run.pruned = pruned;
+    }
+
+    Ok(run)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_policy() {
+        assert!(policy(&toml::from_str("[updater]").unwrap()).unwrap().is_none());
+        assert_eq!(Some(Policy { full_resolution_days: 30, hourly_days: None, days_per_run: 7 }),
+                   policy(&toml::from_str("[retention]").unwrap()).unwrap());
+        assert_eq!(Some(Policy { full_resolution_days: 1, hourly_days: Some(365), days_per_run: 1 }),
+                   policy(&toml::from_str("[retention]\nfull_resolution_days = 0\nhourly_days = 365\ndays_per_run = -1").unwrap()).unwrap());
+        assert!(policy(&toml::from_str("[retention]\nfull_resolution_days = 90\nhourly_days = 30").unwrap()).is_err());
+    }
+}
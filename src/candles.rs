@@ -0,0 +1,196 @@
+use chrono::NaiveDateTime;
+use postgres::{error::SqlState, types::ToSql, Error, Row};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::db::DB;
+
+
+// Number of candles returned when no start time is given.
+const DEFAULT_CANDLES: i32 = 100;
+// View of raw prices followed by the opens of downsampled candles, see `create_tables`.
+pub const HISTORY: &str = "price_history";
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interval {
+    FifteenMinutes,
+    Hour,
+    FourHours,
+    Day,
+}
+
+
+impl Interval {
+    pub fn parse(interval: &str) -> Option<Interval> {
+        match interval.to_lowercase().as_str() {
+            "15m" => Some(Interval::FifteenMinutes),
+            "1h" => Some(Interval::Hour),
+            "4h" => Some(Interval::FourHours),
+            "1d" => Some(Interval::Day),
+            _ => None
+        }
+    }
+
+    pub fn seconds(self) -> i32 {
+        match self {
+            Interval::FifteenMinutes => 15 * 60,
+            Interval::Hour => 60 * 60,
+            Interval::FourHours => 4 * 60 * 60,
+            Interval::Day => 24 * 60 * 60,
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Candle {
+    pub time: NaiveDateTime,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+}
+
+
+// Prices older than the updater's retention window are downsampled into hourly candles per coin, which are later
+// rolled into daily candles. `price_history` lets readers see each candle's open, its first price, as a price at the
+// candle's start.
+pub fn create_tables(db: &mut DB) -> Result<(), Error> {
+    db.batch_execute("create_candles",
+        "create table if not exists hourly_candles(
+            coin_id integer not null references coins,
+            time timestamp not null,
+            open_euro numeric not null,
+            high_euro numeric not null,
+            low_euro numeric not null,
+            close_euro numeric not null,
+            open_dollar numeric not null,
+            high_dollar numeric not null,
+            low_dollar numeric not null,
+            close_dollar numeric not null,
+            primary key (coin_id, time)
+        );
+        create table if not exists daily_candles(
+            coin_id integer not null references coins,
+            date date not null,
+            open_euro numeric not null,
+            high_euro numeric not null,
+            low_euro numeric not null,
+            close_euro numeric not null,
+            open_dollar numeric not null,
+            high_dollar numeric not null,
+            low_dollar numeric not null,
+            close_dollar numeric not null,
+            primary key (coin_id, date)
+        );
+        create or replace view price_history as
+            select coin_id, time, euro, dollar from prices
+            union all
+            select coin_id, time, open_euro, open_dollar from hourly_candles
+            union all
+            select coin_id, date::timestamp, open_euro, open_dollar from daily_candles;")
+}
+
+
+// Runs a query over `HISTORY`, or over `prices` when retention hasn't created it yet. `sql` formats the query with
+// the table to read from.
+pub fn query_history(db: &mut DB, name: &str, sql: impl Fn(&str) -> String, params: &[&(dyn ToSql + Sync)])
+                     -> Result<Vec<Row>, Error> {
+    match db.query(name, &sql(HISTORY), params) {
+        Err(e) if e.code() == Some(&SqlState::UNDEFINED_TABLE) => db.query(name, &sql("prices"), params),
+        rows => rows
+    }
+}
+
+
+// `HISTORY` when retention has created it and `prices` otherwise, for readers that stream rows and can't retry.
+pub fn history_source(db: &mut DB) -> Result<&'static str, Error> {
+    let rows = db.query("history_source", "select to_regclass($1::text) is not null", &[&HISTORY])?;
+    Ok(if rows[0].get(0) { HISTORY } else { "prices" })
+}
+
+
+// Hourly and daily candles fill in hour or longer and day long intervals where `prices` has been downsampled.
+fn sql(downsampled: bool) -> String {
+    let downsampled = match downsampled {
+        true =>
+            "union all
+            select time, case when $5 then open_dollar else open_euro end, case when $5 then high_dollar else high_euro end,
+            case when $5 then low_dollar else low_euro end, case when $5 then close_dollar else close_euro end
+            from hourly_candles
+            join coins using(coin_id)
+            where name = $1
+            and $2::int >= 3600
+            union all
+            select date::timestamp, case when $5 then open_dollar else open_euro end,
+            case when $5 then high_dollar else high_euro end, case when $5 then low_dollar else low_euro end,
+            case when $5 then close_dollar else close_euro end
+            from daily_candles
+            join coins using(coin_id)
+            where name = $1
+            and $2::int >= 86400",
+        false => ""
+    };
+
+    format!(
+        "with bucketed as (
+            select to_timestamp(floor(extract(epoch from time) / $2::int) * $2::int) at time zone 'UTC' as bucket,
+            time, open, high, low, close
+            from (
+                select time, price as open, price as high, price as low, price as close
+                from (
+                    select time, case when $5 then dollar else euro end as price
+                    from prices
+                    join coins using(coin_id)
+                    where name = $1
+                ) p
+                {}
+            ) c
+            where time >= coalesce($3::timestamp, now()::timestamp - $2::int * $6::int * interval '1 second')
+            and ($4::timestamp is null or time < $4::timestamp)
+        )
+        select bucket, (array_agg(open order by time asc))[1], max(high), min(low),
+        (array_agg(close order by time desc))[1]
+        from bucketed
+        group by bucket
+        order by bucket asc", downsampled)
+}
+
+
+// Candles for a coin aggregated from `prices`, `hourly_candles` and `daily_candles`, oldest first. Buckets are aligned
+// to the epoch so the same interval always produces the same boundaries. `dollar` selects USD prices instead of EUR.
+pub fn query(db: &mut DB, coin: &str, interval: Interval, from: Option<NaiveDateTime>, to: Option<NaiveDateTime>,
+             dollar: bool) -> Result<Vec<Candle>, Error> {
+    let params: [&(dyn ToSql + Sync); 6] = [&coin, &interval.seconds(), &from, &to, &dollar, &DEFAULT_CANDLES];
+    let rows = match db.query("candles", &sql(true), &params) {
+        Err(e) if e.code() == Some(&SqlState::UNDEFINED_TABLE) => db.query("candles", &sql(false), &params)?,
+        rows => rows?
+    };
+
+    Ok(rows.iter()
+        .map(|row| Candle {
+                time: row.get(0),
+                open: row.get(1),
+                high: row.get(2),
+                low: row.get(3),
+                close: row.get(4),
+            }
+        )
+        .collect())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_intervals() {
+        assert_eq!(Some(900), Interval::parse("15m").map(Interval::seconds));
+        assert_eq!(Some(3600), Interval::parse("1H").map(Interval::seconds));
+        assert_eq!(Some(14400), Interval::parse("4h").map(Interval::seconds));
+        assert_eq!(Some(86400), Interval::parse("1d").map(Interval::seconds));
+        assert_eq!(None, Interval::parse("5m"));
+    }
+}
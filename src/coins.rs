@@ -0,0 +1,83 @@
+use chrono::NaiveDateTime;
+use postgres::Error;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::db::DB;
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Coin {
+    pub name: String,
+    pub ticker: String,
+}
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Metadata {
+    pub name: String,
+    pub ticker: String,
+    pub rank: Option<i32>,
+    pub market_cap: Option<Decimal>,
+    pub volume_24h: Option<Decimal>,
+    pub circulating_supply: Option<Decimal>,
+    pub max_supply: Option<Decimal>,
+    pub updated: NaiveDateTime,
+}
+
+
+// Market data kept alongside `coins` and refreshed by the updater from its metadata source. Amounts are in euro.
+pub fn create_metadata_table(db: &mut DB) -> Result<(), Error> {
+    db.batch_execute("create_coin_metadata",
+        "create table if not exists coin_metadata(
+            coin_id integer primary key references coins,
+            rank integer,
+            market_cap numeric,
+            volume_24h numeric,
+            circulating_supply numeric,
+            max_supply numeric,
+            updated timestamp not null default now()
+        );")
+}
+
+
+// Tracked coins ordered by name.
+pub fn list(db: &mut DB, limit: i64, offset: i64) -> Result<Vec<Coin>, Error> {
+    let query =
+        "select name, ticker
+        from coins
+        order by name asc
+        limit $1 offset $2";
+
+    Ok(db.query("coins", query, &[&limit, &offset])?
+        .iter()
+        .map(|row| Coin {
+                name: row.get(0),
+                ticker: row.get(1),
+            }
+        )
+        .collect())
+}
+
+
+pub fn metadata(db: &mut DB, coin: &str) -> Result<Option<Metadata>, Error> {
+    let query =
+        "select name, ticker, rank, market_cap, volume_24h, circulating_supply, max_supply, updated
+        from coin_metadata
+        join coins using(coin_id)
+        where name = $1";
+
+    Ok(db.query("coin_metadata", query, &[&coin])?
+        .first()
+        .map(|row| Metadata {
+                name: row.get(0),
+                ticker: row.get(1),
+                rank: row.get(2),
+                market_cap: row.get(3),
+                volume_24h: row.get(4),
+                circulating_supply: row.get(5),
+                max_supply: row.get(6),
+                updated: row.get(7),
+            }
+        ))
+}
@@ -2,11 +2,16 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::iter::FromIterator;
 
-use postgres::{Client, Error, NoTls};
+use postgres::{error::SqlState, types::ToSql, Client, Error, NoTls, Row, Transaction};
 use toml::Value;
 
+use crate::metrics::DB_QUERY_SECONDS;
 
-fn read_config(path: &str) -> Value {
+
+pub(crate) const CONFIG: &str = "configuration/DB.toml";
+
+
+pub(crate) fn read_config(path: &str) -> Value {
     let toml_content = fs::read_to_string(path)
                         .unwrap_or_else(|_| panic!("Unable to read DB config from: {}", path));
     toml::from_str(&toml_content).unwrap_or_else(|_| panic!("Unable to parse TOML from {}", path))
@@ -22,21 +27,64 @@ pub struct DB {
 
 impl DB {
     pub fn new() -> Result<Self, Error> {
-        let config = read_config("configuration/DB.toml");
-        let mut c = Client::connect(config["database"]["connection"].as_str().unwrap(), NoTls)?;
-        let nicks_coins = DB::get_nicks(&mut c);
-        let all_coins = DB::get_coins(&nicks_coins);
+        let mut db = DB::connect()?;
+        db.nicks_coins = db.get_nicks()?;
+        db.all_coins = DB::get_coins(&db.nicks_coins);
+        Ok(db)
+    }
+
+    // A connection without the coin lookups, for callers that never resolve coin names.
+    pub fn connect() -> Result<Self, Error> {
+        let config = read_config(CONFIG);
+        let c = Client::connect(config["database"]["connection"].as_str().unwrap(), NoTls)?;
 
         Ok(Self {
-            all_coins,
-            nicks_coins,
+            all_coins: HashSet::new(),
+            nicks_coins: HashMap::new(),
             connection: c,
         })
     }
 
-    fn get_nicks(connection: &mut Client) -> HashMap<String, String> {
-        let query = "Select ticker, name from coins";
-        connection.query(query, &[]).unwrap().iter().map(|r| (r.get(0), r.get(1))).collect::<HashMap<String, String>>()
+    // Runs a query, recording its duration under `name`.
+    pub fn query(&mut self, name: &str, query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<Row>, Error> {
+        let _timer = DB_QUERY_SECONDS.with_label_values(&[name]).start_timer();
+        self.connection.query(query, params)
+    }
+
+    // Runs a statement and returns the rows it changed, recording its duration under `name`.
+    pub fn execute(&mut self, name: &str, query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<u64, Error> {
+        let _timer = DB_QUERY_SECONDS.with_label_values(&[name]).start_timer();
+        self.connection.execute(query, params)
+    }
+
+    // Runs statements without parameters, recording their duration under `name`.
+    pub fn batch_execute(&mut self, name: &str, query: &str) -> Result<(), Error> {
+        let _timer = DB_QUERY_SECONDS.with_label_values(&[name]).start_timer();
+        self.connection.batch_execute(query)
+    }
+
+    // Runs `f` in a transaction committed when it succeeds, recording the whole transaction's duration under `name`.
+    pub fn transaction<T, E: From<Error>>(&mut self, name: &str, f: impl FnOnce(&mut Transaction) -> Result<T, E>)
+                                          -> Result<T, E> {
+        let _timer = DB_QUERY_SECONDS.with_label_values(&[name]).start_timer();
+        let mut transaction = self.connection.transaction()?;
+        let result = f(&mut transaction)?;
+        transaction.commit()?;
+        Ok(result)
+    }
+
+    // When tickers collide the highest ranked coin wins, so rows are ordered with it last.
+    fn get_nicks(&mut self) -> Result<HashMap<String, String>, Error> {
+        let query =
+            "Select ticker, name from coins
+            left join coin_metadata using(coin_id)
+            order by rank desc nulls first, coin_id desc";
+        let rows = match self.query("nicks", query, &[]) {
+            Err(e) if e.code() == Some(&SqlState::UNDEFINED_TABLE) => self.query("nicks", "Select ticker, name from coins", &[])?,
+            rows => rows?
+        };
+
+        Ok(rows.iter().map(|r| (r.get(0), r.get(1))).collect::<HashMap<String, String>>())
     }
 
     fn get_coins(nicks_coins: &HashMap<String, String>) -> HashSet<String> {
@@ -0,0 +1,275 @@
+use std::error::Error;
+use std::io::Write;
+use std::sync::Arc;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use parquet::data_type::{ByteArray, ByteArrayType, FixedLenByteArray, FixedLenByteArrayType, Int32Type, Int64Type};
+use parquet::file::{properties::WriterProperties, writer::SerializedFileWriter};
+use parquet::schema::parser::parse_message_type;
+use postgres::{fallible_iterator::FallibleIterator, Row};
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::candles;
+use crate::db::DB;
+use crate::metrics::DB_QUERY_SECONDS;
+
+
+const ROW_GROUP_SIZE: usize = 100_000;
+// Decimals are written to Parquet as DECIMAL(38, 18), wide enough for any price we store.
+const PARQUET_SCALE: u32 = 18;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Table {
+    Prices,
+    DailyStats,
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Csv,
+    Parquet,
+}
+
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Text,
+    Time,
+    Date,
+    Decimal,
+}
+
+
+enum Value {
+    Text(String),
+    Time(NaiveDateTime),
+    Date(NaiveDate),
+    Decimal(Option<Decimal>),
+}
+
+
+impl Table {
+    pub fn parse(table: &str) -> Option<Table> {
+        match table {
+            "prices" => Some(Table::Prices),
+            "daily_stats" | "stats" => Some(Table::DailyStats),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Table::Prices => "prices",
+            Table::DailyStats => "daily_stats",
+        }
+    }
+
+    fn columns(self) -> &'static [(&'static str, Kind)] {
+        match self {
+            Table::Prices => &[("name", Kind::Text), ("ticker", Kind::Text), ("time", Kind::Time),
+                               ("euro", Kind::Decimal), ("dollar", Kind::Decimal)],
+            Table::DailyStats => &[("name", Kind::Text), ("ticker", Kind::Text), ("date", Kind::Date),
+                                   ("min_euro", Kind::Decimal), ("average_euro", Kind::Decimal),
+                                   ("median_euro", Kind::Decimal), ("std_dev", Kind::Decimal),
+                                   ("max_euro", Kind::Decimal)],
+        }
+    }
+
+    // `prices` is the table or view prices are read from, see `candles::history_source`.
+    fn query(self, prices: &str) -> String {
+        match self {
+            Table::Prices => format!(
+                "select name, ticker, time, euro, dollar
+                from {}
+                join coins using(coin_id)
+                where name = any($1)
+                and time >= $2::date
+                and time < $3::date + 1
+                order by name, time", prices),
+            Table::DailyStats =>
+                "select name, ticker, date, min_euro, average_euro, median_euro, std_dev, max_euro
+                from daily_stats
+                join coins using(coin_id)
+                where name = any($1)
+                and date between $2 and $3
+                order by name, date".to_string(),
+        }
+    }
+}
+
+
+impl Format {
+    pub fn parse(format: &str) -> Option<Format> {
+        match format {
+            "csv" => Some(Format::Csv),
+            "parquet" => Some(Format::Parquet),
+            _ => None
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Parquet => "parquet",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv",
+            Format::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+
+fn value(row: &Row, index: usize, kind: Kind) -> Value {
+    match kind {
+        Kind::Text => Value::Text(row.get(index)),
+        Kind::Time => Value::Time(row.get(index)),
+        Kind::Date => Value::Date(row.get(index)),
+        Kind::Decimal => Value::Decimal(row.get(index)),
+    }
+}
+
+
+impl Value {
+    fn to_csv(&self) -> String {
+        match self {
+            Value::Text(t) => t.clone(),
+            Value::Time(t) => t.to_string(),
+            Value::Date(d) => d.to_string(),
+            Value::Decimal(d) => d.map_or("".to_string(), |d| d.normalize().to_string()),
+        }
+    }
+}
+
+
+fn parquet_schema(table: Table) -> String {
+    let fields = table.columns().iter().map(|(name, kind)| match kind {
+        Kind::Text => format!("REQUIRED BYTE_ARRAY {} (UTF8);", name),
+        Kind::Time => format!("REQUIRED INT64 {} (TIMESTAMP(MICROS,false));", name),
+        Kind::Date => format!("REQUIRED INT32 {} (DATE);", name),
+        Kind::Decimal => format!("OPTIONAL FIXED_LEN_BYTE_ARRAY (16) {} (DECIMAL(38,{}));", name, PARQUET_SCALE),
+    }).collect::<Vec<_>>();
+
+    format!("message {} {{ {} }}", table.name(), fields.join(" "))
+}
+
+
+// The unscaled value at PARQUET_SCALE as 16 big-endian bytes.
+fn parquet_decimal(value: Decimal) -> Result<FixedLenByteArray, String> {
+    let value = value.round_dp_with_strategy(PARQUET_SCALE, RoundingStrategy::MidpointAwayFromZero);
+    let unscaled = value.mantissa().checked_mul(10i128.pow(PARQUET_SCALE - value.scale()))
+        .ok_or_else(|| format!("{} is too large to export", value))?;
+    Ok(FixedLenByteArray::from(unscaled.to_be_bytes().to_vec()))
+}
+
+
+fn write_row_group<W: Write + Send>(writer: &mut SerializedFileWriter<W>, table: Table, rows: &[Vec<Value>])
+                                    -> Result<(), Box<dyn Error>> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+    let mut group = writer.next_row_group()?;
+    let mut index = 0;
+
+    while let Some(mut column) = group.next_column()? {
+        let values = rows.iter().map(|r| &r[index]);
+        match table.columns()[index].1 {
+            Kind::Text => column.typed::<ByteArrayType>().write_batch(&values.map(|v| match v {
+                Value::Text(t) => ByteArray::from(t.as_bytes().to_vec()),
+                _ => ByteArray::new()
+            }).collect::<Vec<_>>(), None, None)?,
+            Kind::Time => column.typed::<Int64Type>().write_batch(&values.map(|v| match v {
+                Value::Time(t) => t.and_utc().timestamp_micros(),
+                _ => 0
+            }).collect::<Vec<_>>(), None, None)?,
+            Kind::Date => column.typed::<Int32Type>().write_batch(&values.map(|v| match v {
+                Value::Date(d) => (*d - epoch).num_days() as i32,
+                _ => 0
+            }).collect::<Vec<_>>(), None, None)?,
+            Kind::Decimal => {
+                let decimals = values.map(|v| match v {
+                    Value::Decimal(d) => *d,
+                    _ => None
+                }).collect::<Vec<_>>();
+                let levels = decimals.iter().map(|d| d.is_some() as i16).collect::<Vec<_>>();
+                let present = decimals.into_iter().flatten().map(parquet_decimal).collect::<Result<Vec<_>, _>>()?;
+                column.typed::<FixedLenByteArrayType>().write_batch(&present, Some(&levels), None)?
+            }
+        };
+        column.close()?;
+        index += 1;
+    }
+
+    group.close()?;
+    Ok(())
+}
+
+
+// Writes rows for `coins` between `from` and `to`, both inclusive, and returns how many were written. Rows are
+// streamed from Postgres so large ranges don't have to fit in memory.
+pub fn write<W: Write + Send>(db: &mut DB, table: Table, format: Format, coins: &[String], from: NaiveDate,
+                              to: NaiveDate, out: W) -> Result<usize, Box<dyn Error>> {
+    let _timer = DB_QUERY_SECONDS.with_label_values(&[&format!("export_{}", table.name())]).start_timer();
+    let params: [&(dyn postgres::types::ToSql + Sync); 3] = [&coins, &from, &to];
+    let prices = candles::history_source(db)?;
+    let mut rows = db.connection.query_raw(table.query(prices).as_str(), params.iter().copied())?;
+    let columns = table.columns();
+    let mut count = 0;
+
+    match format {
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record(columns.iter().map(|c| c.0))?;
+            while let Some(row) = rows.next()? {
+                writer.write_record(columns.iter().enumerate().map(|(i, c)| value(&row, i, c.1).to_csv()))?;
+                count += 1;
+            }
+            writer.flush()?;
+        },
+        Format::Parquet => {
+            let schema = Arc::new(parse_message_type(&parquet_schema(table))?);
+            let mut writer = SerializedFileWriter::new(out, schema, Arc::new(WriterProperties::builder().build()))?;
+            let mut group = Vec::with_capacity(ROW_GROUP_SIZE);
+            while let Some(row) = rows.next()? {
+                group.push(columns.iter().enumerate().map(|(i, c)| value(&row, i, c.1)).collect::<Vec<_>>());
+                if group.len() == ROW_GROUP_SIZE {
+                    write_row_group(&mut writer, table, &group)?;
+                    count += group.len();
+                    group.clear();
+                }
+            }
+            if !group.is_empty() || count == 0 {
+                write_row_group(&mut writer, table, &group)?;
+                count += group.len();
+            }
+            writer.close()?;
+        }
+    }
+
+    Ok(count)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemas_parse() {
+        for table in &[Table::Prices, Table::DailyStats] {
+            let schema = parse_message_type(&parquet_schema(*table)).unwrap();
+            assert_eq!(table.columns().len(), schema.get_fields().len());
+        }
+    }
+
+    #[test]
+    fn decimals_at_parquet_scale() {
+        let bytes = |d: &str| parquet_decimal(d.parse().unwrap()).unwrap().data().to_vec();
+        assert_eq!(1_234_500_000_000_000_000_000i128.to_be_bytes().to_vec(), bytes("1234.5"));
+        assert_eq!(1i128.to_be_bytes().to_vec(), bytes("0.000000000000000001"));
+        assert_eq!(1i128.to_be_bytes().to_vec(), bytes("0.0000000000000000005"));
+    }
+}
@@ -1 +1,12 @@
+pub mod api_keys;
+pub mod ats;
+pub mod candles;
+pub mod coins;
 pub mod db;
+pub mod export;
+pub mod market;
+pub mod metrics;
+pub mod movers;
+pub mod prices;
+pub mod stats;
+pub mod window;
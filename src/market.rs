@@ -0,0 +1,63 @@
+use postgres::Error;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::db::DB;
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Overview {
+    pub market_cap: Decimal,
+    // Percentage change in market cap over 24 hours, for coins priced at both ends.
+    pub change: Option<f32>,
+    pub volume_24h: Option<Decimal>,
+    pub bitcoin_dominance: Option<f32>,
+    pub ethereum_dominance: Option<f32>,
+    // Coins with a price in the last day.
+    pub coins: i64,
+}
+
+
+// Totals across coins with a known circulating supply. Market caps come from the latest stored euro price times
+// the supply so they move with every update, rather than waiting for the next metadata refresh.
+pub fn overview(db: &mut DB) -> Result<Option<Overview>, Error> {
+    let query =
+        "with latest as (
+            select distinct on (coin_id) coin_id, euro
+            from prices
+            where time >= now()::timestamp - interval '1 day'
+            order by coin_id, time desc
+        ),
+        previous as (
+            select distinct on (coin_id) coin_id, euro
+            from prices
+            where time >= now()::timestamp - interval '25 hours'
+            and time < now()::timestamp - interval '24 hours'
+            order by coin_id, time desc
+        ),
+        caps as (
+            select name, l.euro * circulating_supply as cap, p.euro * circulating_supply as previous_cap, volume_24h
+            from coin_metadata
+            join coins using(coin_id)
+            join latest l using(coin_id)
+            left join previous p using(coin_id)
+            where circulating_supply > 0
+        )
+        select round(sum(cap), 2),
+        cast((sum(cap) filter (where previous_cap is not null) - sum(previous_cap)) * 100 / nullif(sum(previous_cap), 0) as real),
+        round(sum(volume_24h), 2),
+        cast(sum(cap) filter (where name = 'bitcoin') * 100 / nullif(sum(cap), 0) as real),
+        cast(sum(cap) filter (where name = 'ethereum') * 100 / nullif(sum(cap), 0) as real),
+        (select count(*) from latest)
+        from caps";
+
+    let rows = db.query("market_overview", query, &[])?;
+    Ok(rows.first().and_then(|row| Some(Overview {
+        market_cap: row.get::<_, Option<Decimal>>(0)?,
+        change: row.get(1),
+        volume_24h: row.get(2),
+        bitcoin_dominance: row.get(3),
+        ethereum_dominance: row.get(4),
+        coins: row.get(5),
+    })))
+}
@@ -0,0 +1,54 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use lazy_static::lazy_static;
+use log::{error, info};
+use prometheus::{register_histogram_vec, Encoder, HistogramVec, TextEncoder};
+
+
+lazy_static! {
+    pub static ref DB_QUERY_SECONDS: HistogramVec = register_histogram_vec!(
+        "rooney_db_query_duration_seconds", "Time spent running database queries.", &["query"]
+    ).unwrap();
+}
+
+
+// Every metric registered by this process in the Prometheus text format.
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        error!("Unable to encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+
+// Serves `render()` on `address` from a background thread for binaries without an HTTP server.
+pub fn serve(address: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    info!("Serving metrics on {}", address);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("Metrics connection failed: {}", e);
+                    continue;
+                }
+            };
+
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let body = render();
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+                                    Connection: close\r\n\r\n{}", body.len(), body);
+            if let Err(e) = stream.write_all(response.as_bytes()) {
+                error!("Unable to send metrics: {}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
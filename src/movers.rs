@@ -0,0 +1,43 @@
+use postgres::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::candles;
+use crate::db::DB;
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Mover {
+    pub name: String,
+    pub ticker: String,
+    pub change: f32,
+}
+
+
+// Coins ranked by their change since `date_trunc(unit, latest) - interval`, where latest is the newest price so
+// the window doesn't empty out after midnight or while the updater lags. Direction is 1.0 for the biggest gains
+// first and -1.0 for the biggest losses first.
+pub fn query(db: &mut DB, unit: &str, interval: &str, direction: f32, limit: i64, offset: i64)
+             -> Result<Vec<Mover>, Error> {
+    let query = |prices: &str| format!(
+        "with movers as (
+            select distinct coin_id, first_value(euro) over w as first, last_value(euro) over w as last
+            from {0} where time >= date_trunc($1, (select max(time) from {0})) - $2::text::interval WINDOW w as (
+                partition by coin_id order by time range between unbounded preceding and unbounded
+                following) order by coin_id
+        )
+        select name, ticker, cast((last-first)*100/first as real) as diff
+        from movers
+        join coins using(coin_id)
+        where first > 0
+        order by cast((last-first)*100/first as real) * $3::real desc, name asc limit $4 offset $5", prices);
+
+    Ok(candles::query_history(db, "movers", query, &[&unit, &interval, &direction, &limit, &offset])?
+        .iter()
+        .map(|row| Mover {
+                name: row.get(0),
+                ticker: row.get(1),
+                change: row.get(2),
+            }
+        )
+        .collect())
+}
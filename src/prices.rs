@@ -0,0 +1,223 @@
+use chrono::NaiveDateTime;
+use lazy_static::lazy_static;
+use postgres::Error;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::candles;
+use crate::db::{self, DB};
+
+
+// Channel the updater notifies after each batch of prices is written.
+pub const CHANNEL: &str = "prices";
+
+
+lazy_static! {
+    // Prices older than this are flagged by the bot and fail the API's readiness check, set by stale_after_minutes
+    // in the [prices] section of DB.toml.
+    pub static ref STALE_AFTER_SECONDS: i64 = db::read_config(db::CONFIG).get("prices")
+        .and_then(|p| p.get("stale_after_minutes"))
+        .and_then(|m| m.as_integer())
+        .unwrap_or(15) * 60;
+}
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Price {
+    pub name: String,
+    pub ticker: String,
+    pub euro: Decimal,
+    pub dollar: Decimal,
+    pub time: NaiveDateTime,
+}
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Summary {
+    pub name: String,
+    pub ticker: String,
+    pub euro: Decimal,
+    pub dollar: Decimal,
+    pub min: Decimal,
+    pub max: Decimal,
+    // Percentage change since the start of the window.
+    pub change: f32,
+    pub median: Decimal,
+    pub time: NaiveDateTime,
+    // Seconds since the latest price was written.
+    pub age: i64,
+}
+
+
+// Prices for a coin since `date_trunc(unit, now()) - interval`, oldest first.
+pub fn query(db: &mut DB, coin: &str, unit: &str, interval: &str) -> Result<Vec<Price>, Error> {
+    let query = |prices: &str| format!(
+        "select name, ticker, euro, dollar, time
+        from {}
+        join coins using(coin_id)
+        where time >= date_trunc($2, now()::timestamp) - $3::text::interval
+        and name = $1
+        order by time asc", prices);
+
+    Ok(candles::query_history(db, "prices_query", query, &[&coin, &unit, &interval])?
+        .iter()
+        .map(|row| Price {
+                name: row.get(0),
+                ticker: row.get(1),
+                euro: row.get(2),
+                dollar: row.get(3),
+                time: row.get(4),
+            }
+        )
+        .collect())
+}
+
+
+// Seconds since the newest price was written, None when there are no prices.
+pub fn age(db: &mut DB) -> Result<Option<i64>, Error> {
+    let query = "select extract(epoch from now()::timestamp - max(time))::bigint from prices";
+    Ok(db.query("prices_age", query, &[])?[0].get(0))
+}
+
+
+// Most recent price for each coin.
+pub fn latest(db: &mut DB, coins: &[String]) -> Result<Vec<Price>, Error> {
+    let query =
+        "select distinct on (name) name, ticker, euro, dollar, time
+        from prices
+        join coins using(coin_id)
+        where name = any($1)
+        and time >= now()::timestamp - interval '1 day'
+        order by name, time desc";
+
+    Ok(db.query("prices_latest", query, &[&coins])?
+        .iter()
+        .map(|row| Price {
+                name: row.get(0),
+                ticker: row.get(1),
+                euro: row.get(2),
+                dollar: row.get(3),
+                time: row.get(4),
+            }
+        )
+        .collect())
+}
+
+
+// Newest price for each coin however old it is, along with its age in seconds.
+pub fn last_known(db: &mut DB, coins: &[String]) -> Result<Vec<(Price, i64)>, Error> {
+    let query = |prices: &str| format!(
+        "select distinct on (name) name, ticker, euro, dollar, time, extract(epoch from now()::timestamp - time)::bigint
+        from {}
+        join coins using(coin_id)
+        where name = any($1)
+        order by name, time desc", prices);
+
+    Ok(candles::query_history(db, "prices_last_known", query, &[&coins])?
+        .iter()
+        .map(|row| (Price {
+                name: row.get(0),
+                ticker: row.get(1),
+                euro: row.get(2),
+                dollar: row.get(3),
+                time: row.get(4),
+            }, row.get(5))
+        )
+        .collect())
+}
+
+
+// Prices for a coin between `from` (default 24 hours ago) and `to`, oldest first. When `bucket` is given in seconds
+// only the last price in each epoch aligned bucket is returned. Downsampled days come back as candle opens.
+pub fn history(db: &mut DB, coin: &str, from: Option<NaiveDateTime>, to: Option<NaiveDateTime>, bucket: Option<i32>,
+               limit: i64, offset: i64) -> Result<Vec<Price>, Error> {
+    let query = |prices: &str| format!(
+        "with bucketed as (
+            select distinct on (coalesce(floor(extract(epoch from time) / $4::int), extract(epoch from time)))
+            name, ticker, euro, dollar, time
+            from {}
+            join coins using(coin_id)
+            where name = $1
+            and time >= coalesce($2::timestamp, now()::timestamp - interval '24 hours')
+            and ($3::timestamp is null or time < $3::timestamp)
+            order by coalesce(floor(extract(epoch from time) / $4::int), extract(epoch from time)) asc, time desc
+        )
+        select name, ticker, euro, dollar, time
+        from bucketed
+        order by time asc
+        limit $5 offset $6", prices);
+
+    Ok(candles::query_history(db, "prices_history", query, &[&coin, &from, &to, &bucket, &limit, &offset])?
+        .iter()
+        .map(|row| Price {
+                name: row.get(0),
+                ticker: row.get(1),
+                euro: row.get(2),
+                dollar: row.get(3),
+                time: row.get(4),
+            }
+        )
+        .collect())
+}
+
+
+// Latest price for each coin along with its change, low, high and median since `date_trunc(unit, now()) - interval`.
+pub fn summaries(db: &mut DB, coins: &[String], unit: &str, interval: &str) -> Result<Vec<Summary>, Error> {
+    let query = |prices: &str| format!(
+        "with window_prices as (
+            select * from {}
+            join coins using(coin_id)
+            where time >= date_trunc($2, now()::timestamp) - $3::text::interval
+            and name = any($1)
+        ),
+        min_max_prices as (
+            select name, max(euro), min(euro)
+            from window_prices
+            group by name
+        ),
+        all_prices as (
+            select row_number() over (partition by name order by time desc) rn,
+            row_number() over (partition by name order by time asc) first_rn,
+            name, ticker, euro, dollar, time
+            from window_prices
+        ),
+        median_prices as (
+            select name, median(euro) as median
+            from all_prices
+            group by name
+        ),
+        latest_prices as (
+            select name, ticker, euro, dollar, time
+            from all_prices
+            where rn = 1
+        ),
+        first_price as (
+            select name, euro
+            from all_prices
+            where first_rn = 1
+        )
+        select name, ticker, lp.euro, dollar, min, max, cast(((lp.euro - fp.euro)*100)/fp.euro as real),
+        cast(median as numeric), time,
+        extract(epoch from now()::timestamp - time)::bigint
+        from latest_prices as lp
+        join min_max_prices using(name)
+        join first_price as fp using(name)
+        join median_prices using(name)", prices);
+
+    Ok(candles::query_history(db, "prices_summaries", query, &[&coins, &unit, &interval])?
+        .iter()
+        .map(|row| Summary {
+                name: row.get(0),
+                ticker: row.get(1),
+                euro: row.get(2),
+                dollar: row.get(3),
+                min: row.get(4),
+                max: row.get(5),
+                change: row.get(6),
+                median: row.get(7),
+                time: row.get(8),
+                age: row.get(9),
+            }
+        )
+        .collect())
+}
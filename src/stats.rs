@@ -0,0 +1,45 @@
+use chrono::NaiveDate;
+use postgres::Error;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::db::DB;
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DailyStats {
+    pub name: String,
+    pub ticker: String,
+    pub date: NaiveDate,
+    pub min: Decimal,
+    pub average: Decimal,
+    pub median: Decimal,
+    pub std_dev: Decimal,
+    pub max: Decimal,
+}
+
+
+// Euro statistics from `daily_stats` for each coin on a day.
+pub fn query(db: &mut DB, coins: &[String], date: NaiveDate) -> Result<Vec<DailyStats>, Error> {
+    let query =
+        "select name, ticker, date, min_euro, average_euro, median_euro, std_dev, max_euro
+            from daily_stats
+            join coins using(coin_id)
+            where name = any($1)
+            and date = $2";
+
+    Ok(db.query("daily_stats", query, &[&coins, &date])?
+        .iter()
+        .map(|row| DailyStats {
+                name: row.get(0),
+                ticker: row.get(1),
+                date: row.get(2),
+                min: row.get(3),
+                average: row.get(4),
+                median: row.get(5),
+                std_dev: row.get(6),
+                max: row.get(7),
+            }
+        )
+        .collect())
+}
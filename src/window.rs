@@ -0,0 +1,113 @@
+use std::str::FromStr;
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Window {
+    // Arguments for `date_trunc($unit, now()) - $interval`, the start of the window.
+    pub unit: &'static str,
+    pub interval: String,
+    pub arg: String,
+    pub short: String,
+    pub long: String,
+}
+
+
+impl Window {
+    pub fn today() -> Self {
+        Self {
+            unit: "day",
+            interval: "0 days".to_string(),
+            arg: "today".to_string(),
+            short: "Today's".to_string(),
+            long: "Today".to_string(),
+        }
+    }
+
+    pub fn last_24_hours() -> Self {
+        Self::relative(24, "h").unwrap()
+    }
+
+    pub fn parse(word: &str) -> Option<Self> {
+        let word = word.to_lowercase();
+
+        match word.as_str() {
+            "today" => return Some(Self::today()),
+            "ytd" => return Some(Self {
+                unit: "year",
+                interval: "0 days".to_string(),
+                arg: "ytd".to_string(),
+                short: "YTD".to_string(),
+                long: "Year to date".to_string(),
+            }),
+            _ => ()
+        }
+
+        let unit = word.chars().last()?;
+        let amount = u32::from_str(&word[..word.len() - unit.len_utf8()]).ok().filter(|a| *a > 0)?;
+        Self::relative(amount, &word[word.len() - unit.len_utf8()..])
+    }
+
+    // Windows are capped at ten years so they always make a valid Postgres interval.
+    fn relative(amount: u32, unit: &str) -> Option<Self> {
+        let (name, max) = match unit {
+            "h" => ("hour", 10 * 366 * 24),
+            "d" => ("day", 10 * 366),
+            "w" => ("week", 10 * 53),
+            "m" => ("month", 10 * 12),
+            "y" => ("year", 10),
+            _ => return None
+        };
+        if amount > max {
+            return None;
+        }
+        let plural = if amount == 1 { "" } else { "s" };
+        let short = format!("{}{}", amount, unit);
+
+        Some(Self {
+            unit: "microseconds",
+            interval: format!("{} {}{}", amount, name, plural),
+            arg: short.clone(),
+            short,
+            long: format!("Last {} {}{}", amount, name, plural),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! window_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (input, expected): (&str, Option<(&str, &str, &str)>) = $value;
+                let window = Window::parse(input);
+                assert_eq!(expected, window.as_ref().map(|w| (w.unit, w.interval.as_str(), w.long.as_str())));
+            }
+        )*
+        }
+    }
+
+    window_tests! {
+        window_0: ("1h", Some(("microseconds", "1 hour", "Last 1 hour"))),
+        window_1: ("24h", Some(("microseconds", "24 hours", "Last 24 hours"))),
+        window_2: ("7D", Some(("microseconds", "7 days", "Last 7 days"))),
+        window_3: ("3m", Some(("microseconds", "3 months", "Last 3 months"))),
+        window_4: ("ytd", Some(("year", "0 days", "Year to date"))),
+        window_5: ("today", Some(("day", "0 days", "Today"))),
+        window_6: ("btc", None),
+        window_7: ("0d", None),
+        window_8: ("5", None),
+        window_9: ("10y", Some(("microseconds", "10 years", "Last 10 years"))),
+        window_10: ("11y", None),
+        window_11: ("999999999y", None),
+        window_12: ("121m", None),
+        window_13: ("3661d", None),
+        window_14: ("87841h", None),
+        window_15: ("531w", None),
+        window_16: ("99999999999d", None),
+    }
+}